@@ -0,0 +1,13 @@
+use std::env;
+use std::path::Path;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let reset = args.iter().any(|arg| arg == "--reset");
+    let mapping = args.iter()
+        .find(|arg| !arg.starts_with("--"))
+        .expect("Usage: import <mapping.toml> [--reset]");
+
+    let report = dataset_importer::import(Path::new(mapping), reset);
+    println!("{}", report);
+}
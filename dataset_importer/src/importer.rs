@@ -0,0 +1,642 @@
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::path::Path;
+
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::pg::{Pg, PgConnection};
+use diesel::query_builder::{AstPass, QueryFragment, QueryId};
+use diesel::sql_types::{BigInt, Bool, Double, Text};
+use encoding_rs::Encoding;
+use encoding_rs_io::DecodeReaderBytesBuilder;
+use indicatif::ProgressIterator;
+
+use db_manager::import::{insert_chunk_size, ImportReport};
+use db_manager::location::parse_location;
+
+use crate::mapping::{DatasetMapping, EntityMapping, RatingsMapping, FileFormat, Column, ColumnType, LocationPart, DuplicateRatings};
+
+table! {
+    load_checkpoints (stage) {
+        stage -> Varchar,
+        fingerprint -> Varchar,
+        last_key -> Varchar,
+    }
+}
+
+/// A value of the file parsed as the type of its column.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Boolean(bool)
+}
+
+impl Value {
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(value) => Some(*value as f64),
+            Value::Float(value) => Some(*value),
+            _ => None
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Integer(value) => write!(f, "{}", value),
+            Value::Float(value) => write!(f, "{}", value),
+            Value::Text(value) => write!(f, "{}", value),
+            Value::Boolean(value) => write!(f, "{}", value)
+        }
+    }
+}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Null => {}
+            Value::Integer(value) => value.hash(state),
+            Value::Float(value) => value.to_bits().hash(state),
+            Value::Text(value) => value.hash(state),
+            Value::Boolean(value) => value.hash(state)
+        }
+    }
+}
+
+/// A parsed row of the file, in the order of the columns it's inserted into.
+type Row = Vec<Value>;
+
+/// A multi-row `INSERT` binding every value as a parameter of its type.
+struct InsertRows<'a> {
+    table: &'a str,
+    columns: &'a [String],
+    rows: &'a [Row],
+    conflict_clause: &'a str
+}
+
+impl QueryFragment<Pg> for InsertRows<'_> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("INSERT INTO ");
+        out.push_identifier(self.table)?;
+        out.push_sql(" (");
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            out.push_identifier(column)?;
+        }
+        out.push_sql(") VALUES ");
+
+        for (i, row) in self.rows.iter().enumerate() {
+            out.push_sql(if i > 0 { ", (" } else { "(" });
+            for (j, value) in row.iter().enumerate() {
+                if j > 0 {
+                    out.push_sql(", ");
+                }
+                match value {
+                    Value::Null => out.push_sql("NULL"),
+                    Value::Integer(value) => out.push_bind_param::<BigInt, _>(value)?,
+                    Value::Float(value) => out.push_bind_param::<Double, _>(value)?,
+                    Value::Text(value) => out.push_bind_param::<Text, _>(value)?,
+                    Value::Boolean(value) => out.push_bind_param::<Bool, _>(value)?
+                }
+            }
+            out.push_sql(")");
+        }
+
+        out.push_sql(" ");
+        out.push_sql(self.conflict_clause);
+        Ok(())
+    }
+}
+
+impl QueryId for InsertRows<'_> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl RunQueryDsl<PgConnection> for InsertRows<'_> {}
+
+/// Rows that can't be loaded: counted in the report and written to the reject file when there is one.
+struct Rejects {
+    writer: Option<csv::Writer<File>>
+}
+
+impl Rejects {
+    fn create(path: Option<&Path>) -> Rejects {
+        let writer = path.map(|path| {
+            let mut writer = csv::Writer::from_path(path)
+                .unwrap_or_else(|error| panic!("Couldn't create the reject file {}: {}", path.display(), error));
+            writer.write_record(["table", "file", "line", "reason", "record"]).expect("Failed to write the reject file");
+            writer
+        });
+
+        Rejects{writer}
+    }
+
+    fn reject(&mut self, report: &mut ImportReport, table: &str, format: &FileFormat, line: Option<u64>, reason: &str, record: Option<&csv::StringRecord>) {
+        report.record_dropped(reason);
+
+        if let Some(writer) = &mut self.writer {
+            let line = line.map(|line| line.to_string()).unwrap_or_default();
+            let record = record
+                .map(|record| record.iter().collect::<Vec<_>>().join(&format.delimiter.to_string()))
+                .unwrap_or_default();
+            writer.write_record([table, format.file.as_str(), &line, reason, &record]).expect("Failed to write the reject file");
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(writer) = &mut self.writer {
+            writer.flush().expect("Failed to write the reject file");
+        }
+    }
+}
+
+/// Loads the dataset described by the mapping file. Users and items are upserted and every chunk
+/// is committed together with its checkpoint, so running it again after a failure resumes the load.
+/// The checkpoints are deleted once every table is loaded.
+pub fn import(mapping_path: &Path, reset: bool) -> ImportReport {
+    let mapping = DatasetMapping::from_file(mapping_path);
+    let base_dir = mapping_path.parent().unwrap_or_else(|| Path::new("."));
+
+    let connector = PgConnection::establish(&mapping.database_url).expect("Failed connection to database. Maybe the URL?");
+    let has_checkpoints = diesel::select(sql::<Bool>("to_regclass('load_checkpoints') IS NOT NULL"))
+        .get_result::<bool>(&connector)
+        .expect("Failed to look for the load checkpoints table");
+    if !has_checkpoints {
+        panic!("{} has no load_checkpoints table, run the migrations of its manager first", mapping.database_url);
+    }
+
+    if reset {
+        let tables = [&mapping.ratings.table, &mapping.items.table, &mapping.users.table].iter()
+            .map(|table| quote_identifier(table))
+            .collect::<Vec<_>>()
+            .join(", ");
+        diesel::sql_query(format!("TRUNCATE {}, load_checkpoints RESTART IDENTITY", tables))
+            .execute(&connector)
+            .expect("Failed to reset the tables");
+    }
+
+    let mut report = ImportReport::default();
+    let reject_path = mapping.reject_file.as_ref().map(|file| base_dir.join(file));
+    let mut rejects = Rejects::create(reject_path.as_deref());
+
+    let (users_ids, users) = read_entities(&mapping.users, base_dir, "user", &mut report, &mut rejects);
+    load_in_chunks(&connector, &mapping.users.table, &entity_columns(&mapping.users), &upsert_clause(&mapping.users), &users, 1, &mut report);
+
+    let (items_ids, items) = read_entities(&mapping.items, base_dir, "item", &mut report, &mut rejects);
+    load_in_chunks(&connector, &mapping.items.table, &entity_columns(&mapping.items), &upsert_clause(&mapping.items), &items, 1, &mut report);
+
+    advance_id_sequence(&connector, &mapping.users);
+    advance_id_sequence(&connector, &mapping.items);
+
+    let ratings = read_ratings(&mapping.ratings, base_dir, &users_ids, &items_ids, &mut report, &mut rejects);
+    rejects.finish();
+    load_in_chunks(&connector, &mapping.ratings.table, &ratings_columns(&mapping.ratings), &ratings_upsert_clause(&mapping.ratings), &ratings, 2, &mut report);
+
+    diesel::delete(load_checkpoints::table)
+        .execute(&connector)
+        .expect("Failed to delete the load checkpoints");
+
+    if let Some(reject_path) = reject_path {
+        if report.total_dropped() > 0 {
+            println!("Rejected rows written to {}", reject_path.display());
+        }
+    }
+
+    report
+}
+
+/// Reads and checks the source files without touching the database, the report counts
+/// as inserted the rows a load would insert.
+pub fn dry_run(mapping_path: &Path) -> ImportReport {
+    let mapping = DatasetMapping::from_file(mapping_path);
+    let base_dir = mapping_path.parent().unwrap_or_else(|| Path::new("."));
+
+    let mut report = ImportReport::default();
+    let mut rejects = Rejects::create(None);
+
+    let (users_ids, users) = read_entities(&mapping.users, base_dir, "user", &mut report, &mut rejects);
+    report.record_inserted(&mapping.users.table, users.len());
+
+    let (items_ids, items) = read_entities(&mapping.items, base_dir, "item", &mut report, &mut rejects);
+    report.record_inserted(&mapping.items.table, items.len());
+
+    let ratings = read_ratings(&mapping.ratings, base_dir, &users_ids, &items_ids, &mut report, &mut rejects);
+    report.record_inserted(&mapping.ratings.table, ratings.len());
+
+    report
+}
+
+/// Opens a source file decoding it to UTF-8. UTF-8 input is passed through as is,
+/// so invalid bytes end up as unreadable records instead of being silently replaced.
+fn open_source(format: &FileFormat, base_dir: &Path) -> csv::Reader<Box<dyn Read>> {
+    let encoding = Encoding::for_label(format.encoding.as_bytes())
+        .unwrap_or_else(|| panic!("Unsupported encoding {} for {}", format.encoding, format.file));
+    if !format.delimiter.is_ascii() {
+        panic!("The delimiter of {} must be an ASCII character", format.file);
+    }
+    let escape = format.escape.map(|escape| {
+        if !escape.is_ascii() {
+            panic!("The escape character of {} must be an ASCII character", format.file);
+        }
+        escape as u8
+    });
+
+    let path = base_dir.join(&format.file);
+    let file = File::open(&path).unwrap_or_else(|error| panic!("Couldn't load from csv file {}: {}", path.display(), error));
+    let decoded: Box<dyn Read> = Box::new(
+        DecodeReaderBytesBuilder::new()
+            .encoding(Some(encoding))
+            .utf8_passthru(true)
+            .build(file)
+    );
+
+    csv::ReaderBuilder::new()
+        .has_headers(format.has_headers)
+        .delimiter(format.delimiter as u8)
+        .escape(escape)
+        .double_quote(escape.is_none())
+        .flexible(true)
+        .from_reader(decoded)
+}
+
+fn read_entities(mapping: &EntityMapping, base_dir: &Path, entity: &str, report: &mut ImportReport, rejects: &mut Rejects) -> (HashSet<String>, Vec<Row>) {
+    let format = &mapping.format;
+    let mut reader = open_source(format, base_dir);
+    let columns: Vec<&Column> = std::iter::once(&mapping.id).chain(&mapping.attributes).collect();
+
+    let mut ids = HashSet::new();
+    let mut rows = Vec::new();
+
+    for record in reader.records().progress() {
+        let record = match record {
+            Ok(record) => record,
+            Err(error) => {
+                let line = error.position().map(|position| position.line());
+                rejects.reject(report, &mapping.table, format, line, &format!("unreadable {} record", entity), None);
+                continue;
+            }
+        };
+        let line = record.position().map(|position| position.line());
+
+        match render_row(&record, &columns, &format.null_values, entity) {
+            Ok(row) if row[0] == Value::Null => rejects.reject(report, &mapping.table, format, line, &format!("missing {} id", entity), Some(&record)),
+            Ok(row) => {
+                if ids.insert(row[0].to_string()) {
+                    rows.push(row);
+                } else if !mapping.allow_duplicates {
+                    rejects.reject(report, &mapping.table, format, line, &format!("duplicated {} id", entity), Some(&record));
+                }
+            }
+            Err(reason) => rejects.reject(report, &mapping.table, format, line, &reason, Some(&record))
+        }
+    }
+
+    (ids, rows)
+}
+
+fn read_ratings(mapping: &RatingsMapping, base_dir: &Path, users_ids: &HashSet<String>, items_ids: &HashSet<String>, report: &mut ImportReport, rejects: &mut Rejects) -> Vec<Row> {
+    let format = &mapping.format;
+    let mut reader = open_source(format, base_dir);
+    let columns: Vec<&Column> = vec![&mapping.user, &mapping.item, &mapping.rating].into_iter().chain(&mapping.timestamp).collect();
+
+    let mut rows = Vec::new();
+
+    for record in reader.records().progress() {
+        let record = match record {
+            Ok(record) => record,
+            Err(error) => {
+                let line = error.position().map(|position| position.line());
+                rejects.reject(report, &mapping.table, format, line, "unreadable rating record", None);
+                continue;
+            }
+        };
+        let line = record.position().map(|position| position.line());
+
+        match render_row(&record, &columns, &format.null_values, "rating") {
+            Ok(row) => {
+                if !users_ids.contains(&row[0].to_string()) {
+                    rejects.reject(report, &mapping.table, format, line, "rating of unknown user", Some(&record));
+                } else if !items_ids.contains(&row[1].to_string()) {
+                    rejects.reject(report, &mapping.table, format, line, "rating of unknown item", Some(&record));
+                } else {
+                    rows.push(row);
+                }
+            }
+            Err(reason) => rejects.reject(report, &mapping.table, format, line, &reason, Some(&record))
+        }
+    }
+
+    let mut rows = merge_duplicates(rows, mapping.duplicates, report);
+
+    if let Some(implicit) = &mapping.implicit {
+        for row in &mut rows {
+            let is_implicit = row[2].as_f64() == Some(implicit.value);
+            row.push(Value::Boolean(is_implicit));
+        }
+    }
+
+    rows
+}
+
+/// Merges the ratings of the same user and item into the first one found.
+/// Rows hold the user, item, rating and optionally the timestamp.
+fn merge_duplicates(rows: Vec<Row>, policy: DuplicateRatings, report: &mut ImportReport) -> Vec<Row> {
+    let mut merged: Vec<(Row, Vec<f64>)> = Vec::with_capacity(rows.len());
+    let mut positions: HashMap<(String, String), usize> = HashMap::new();
+
+    for row in rows {
+        let rating = row[2].as_f64();
+        let key = (row[0].to_string(), row[1].to_string());
+
+        match positions.get(&key) {
+            Some(&position) => {
+                report.record_dropped("merged duplicated rating");
+
+                let (kept, ratings) = &mut merged[position];
+                ratings.extend(rating);
+                if is_later(&row, kept) {
+                    *kept = row;
+                }
+            }
+            None => {
+                positions.insert(key, merged.len());
+                merged.push((row, rating.into_iter().collect()));
+            }
+        }
+    }
+
+    merged.into_iter()
+        .map(|(mut row, ratings)| {
+            if policy == DuplicateRatings::Average && ratings.len() > 1 {
+                row[2] = Value::Float(ratings.iter().sum::<f64>()/ratings.len() as f64);
+            }
+            row
+        })
+        .collect()
+}
+
+/// Whether `row` is at least as recent as `kept`, rows without timestamp are in file order.
+fn is_later(row: &Row, kept: &Row) -> bool {
+    match (row.get(3), kept.get(3)) {
+        (Some(timestamp), Some(kept_timestamp)) => {
+            match (timestamp.as_f64(), kept_timestamp.as_f64()) {
+                (Some(timestamp), Some(kept_timestamp)) => timestamp >= kept_timestamp,
+                _ => timestamp.to_string() >= kept_timestamp.to_string()
+            }
+        }
+        _ => true
+    }
+}
+
+fn render_row(record: &csv::StringRecord, columns: &[&Column], null_values: &[String], entity: &str) -> Result<Row, String> {
+    let mut row = Vec::with_capacity(columns.len());
+    for column in columns {
+        let value = record.get(column.column).ok_or_else(|| format!("missing {} {}", entity, column.name))?;
+        row.push(render_value(value, column, null_values).ok_or_else(|| format!("invalid {} {}", entity, column.name))?);
+    }
+    Ok(row)
+}
+
+/// Parses a value of the file as the column type, or `None` if it isn't valid.
+fn render_value(value: &str, column: &Column, null_values: &[String]) -> Option<Value> {
+    if column.null.as_deref() == Some(value) || null_values.iter().any(|null| null == value) {
+        return Some(Value::Null);
+    }
+
+    match column.location {
+        Some(part) => match location_part(value, part) {
+            Some(value) => render_typed(&value, column.kind),
+            None => Some(Value::Null)
+        },
+        None => render_typed(value, column.kind)
+    }
+}
+
+fn render_typed(value: &str, kind: ColumnType) -> Option<Value> {
+    match kind {
+        ColumnType::Integer => value.trim().parse::<i64>().ok().map(Value::Integer),
+        ColumnType::Float => value.trim().parse::<f64>().ok().filter(|value| value.is_finite()).map(Value::Float),
+        // Postgres text can't hold NUL characters
+        ColumnType::Text => Some(Value::Text(value.replace('\0', "")))
+    }
+}
+
+fn location_part(value: &str, part: LocationPart) -> Option<String> {
+    let location = parse_location(value);
+    match part {
+        LocationPart::City => location.city,
+        LocationPart::State => location.state,
+        LocationPart::Country => location.country
+    }
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn entity_columns(mapping: &EntityMapping) -> Vec<String> {
+    std::iter::once(&mapping.id).chain(&mapping.attributes).map(|column| column.name.clone()).collect()
+}
+
+fn ratings_columns(mapping: &RatingsMapping) -> Vec<String> {
+    vec![&mapping.user, &mapping.item, &mapping.rating].into_iter()
+        .chain(&mapping.timestamp)
+        .map(|column| column.name.clone())
+        .chain(mapping.implicit.iter().map(|implicit| implicit.name.clone()))
+        .collect()
+}
+
+fn upsert_clause(mapping: &EntityMapping) -> String {
+    let id = quote_identifier(&mapping.id.name);
+    if mapping.attributes.is_empty() {
+        return format!("ON CONFLICT ({}) DO NOTHING", id);
+    }
+
+    let updates = mapping.attributes.iter()
+        .map(|column| {
+            let name = quote_identifier(&column.name);
+            format!("{} = EXCLUDED.{}", name, name)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("ON CONFLICT ({}) DO UPDATE SET {}", id, updates)
+}
+
+/// Ratings are unique per user and item, loading them again replaces the stored ones.
+fn ratings_upsert_clause(mapping: &RatingsMapping) -> String {
+    let columns = ratings_columns(mapping);
+    let updates = columns[2..].iter()
+        .map(|column| {
+            let name = quote_identifier(column);
+            format!("{} = EXCLUDED.{}", name, name)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("ON CONFLICT ({}, {}) DO UPDATE SET {}", quote_identifier(&columns[0]), quote_identifier(&columns[1]), updates)
+}
+
+/// Moves the sequence generating the ids of the table, if there is one, past the ids loaded.
+fn advance_id_sequence(connector: &PgConnection, mapping: &EntityMapping) {
+    if mapping.id.kind != ColumnType::Integer {
+        return;
+    }
+
+    // `setval` of a NULL sequence does nothing
+    let statement = format!(
+        "SELECT setval(pg_get_serial_sequence($1, $2), COALESCE(MAX({}), 0) + 1, false) FROM {}",
+        quote_identifier(&mapping.id.name), quote_identifier(&mapping.table)
+    );
+    diesel::sql_query(statement)
+        .bind::<Text, _>(quote_identifier(&mapping.table))
+        .bind::<Text, _>(&mapping.id.name)
+        .execute(connector)
+        .unwrap_or_else(|error| panic!("Failed to advance the id sequence of {}: {}", mapping.table, error));
+}
+
+/// Identifies the rows to load into a table, a checkpoint left by a load of other rows is ignored.
+/// Rows are hashed with the standard hasher, so a checkpoint may be ignored after a toolchain update.
+fn fingerprint(columns: &[String], rows: &[Row]) -> String {
+    let mut hasher = DefaultHasher::new();
+    columns.hash(&mut hasher);
+    rows.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// The first `key_columns` values of the row, which identify it in its table.
+fn row_key(row: &Row, key_columns: usize) -> String {
+    row[..key_columns].iter().map(Value::to_string).collect::<Vec<_>>().join(",")
+}
+
+/// Rows already loaded by an interrupted load of the same rows: the ones up to the last key committed.
+fn rows_done(connector: &PgConnection, stage: &str, fingerprint: &str, rows: &[Row], key_columns: usize) -> usize {
+    let last_key = load_checkpoints::table.find(stage)
+        .filter(load_checkpoints::fingerprint.eq(fingerprint))
+        .select(load_checkpoints::last_key)
+        .first::<String>(connector)
+        .optional()
+        .expect("Failed query of the load checkpoint");
+
+    last_key
+        .and_then(|last_key| rows.iter().position(|row| row_key(row, key_columns) == last_key))
+        .map_or(0, |position| position + 1)
+}
+
+/// Inserts every chunk in its own transaction together with the checkpoint of the table, which
+/// keeps the key of the last row inserted, so an interrupted load resumes right after it.
+fn load_in_chunks(connector: &PgConnection, table: &str, columns: &[String], conflict_clause: &str, rows: &[Row], key_columns: usize, report: &mut ImportReport) {
+    let fingerprint = fingerprint(columns, rows);
+    let done = rows_done(connector, table, &fingerprint, rows, key_columns);
+    if done > 0 {
+        println!("Resuming {} after {} of {} rows", table, done, rows.len());
+    }
+
+    let chunk_size = insert_chunk_size(columns.len());
+    let pending = &rows[done..];
+
+    for chunk in pending.chunks(chunk_size).progress_count(pending.len().div_ceil(chunk_size) as u64) {
+        let statement = InsertRows{table, columns, rows: chunk, conflict_clause};
+        let last_key = row_key(&chunk[chunk.len() - 1], key_columns);
+
+        let inserted = connector.transaction::<_, diesel::result::Error, _>(|| {
+            let inserted = statement.execute(connector)?;
+            diesel::insert_into(load_checkpoints::table)
+                .values((load_checkpoints::stage.eq(table), load_checkpoints::fingerprint.eq(&fingerprint), load_checkpoints::last_key.eq(&last_key)))
+                .on_conflict(load_checkpoints::stage)
+                .do_update()
+                .set((load_checkpoints::fingerprint.eq(&fingerprint), load_checkpoints::last_key.eq(&last_key)))
+                .execute(connector)?;
+            Ok(inserted)
+        }).unwrap_or_else(|error| panic!("Failed insertion of {} chunk: {}", table, error));
+
+        report.record_inserted(table, inserted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(kind: ColumnType, null: Option<&str>) -> Column {
+        Column{column: 0, name: String::from("value"), kind, null: null.map(String::from), location: None}
+    }
+
+    #[test]
+    fn values_are_parsed_as_the_column_type() {
+        assert_eq!(render_value(" 42", &column(ColumnType::Integer, None), &[]), Some(Value::Integer(42)));
+        assert_eq!(render_value("4.5", &column(ColumnType::Float, None), &[]), Some(Value::Float(4.5)));
+        assert_eq!(render_value("NaN", &column(ColumnType::Float, None), &[]), None);
+        assert_eq!(render_value("Carlo D'Este", &column(ColumnType::Text, None), &[]), Some(Value::Text(String::from("Carlo D'Este"))));
+        assert_eq!(render_value("\\N", &column(ColumnType::Integer, Some("\\N")), &[]), Some(Value::Null));
+        assert_eq!(render_value("abc", &column(ColumnType::Integer, Some("\\N")), &[]), None);
+        assert_eq!(render_value("NULL", &column(ColumnType::Integer, None), &[String::from("NULL")]), Some(Value::Null));
+
+        let country = Column{location: Some(LocationPart::Country), ..column(ColumnType::Text, None)};
+        assert_eq!(render_value("austin, texas, United States", &country, &[]), Some(Value::Text(String::from("usa"))));
+        assert_eq!(render_value("austin, texas, n/a", &country, &[]), Some(Value::Null));
+    }
+
+    #[test]
+    fn mapping_files_parse() {
+        let mapping: DatasetMapping = toml::from_str(r#"
+            database_url = "postgres://localhost/test"
+
+            [users]
+            table = "users"
+            file = "ratings.csv"
+            has_headers = true
+            allow_duplicates = true
+            id = { column = 0, name = "id", type = "integer" }
+
+            [items]
+            table = "movies"
+            file = "movies.csv"
+            id = { column = 0, name = "id", type = "integer" }
+            attributes = [{ column = 1, name = "title" }]
+
+            [ratings]
+            table = "ratings"
+            file = "ratings.csv"
+            delimiter = ";"
+            user = { column = 0, name = "user_id", type = "integer" }
+            item = { column = 1, name = "movie_id", type = "integer" }
+            rating = { column = 2, name = "rating", type = "float" }
+            implicit = { value = 0, name = "implicit" }
+        "#).unwrap();
+
+        assert_eq!(mapping.items.attributes[0].kind, ColumnType::Text);
+        assert_eq!(mapping.ratings.format.delimiter, ';');
+        assert_eq!(mapping.users.format.encoding, "utf-8");
+        assert_eq!(ratings_columns(&mapping.ratings), vec!["user_id", "movie_id", "rating", "implicit"]);
+        assert_eq!(upsert_clause(&mapping.users), "ON CONFLICT (\"id\") DO NOTHING");
+        assert_eq!(upsert_clause(&mapping.items), "ON CONFLICT (\"id\") DO UPDATE SET \"title\" = EXCLUDED.\"title\"");
+    }
+
+    #[test]
+    fn duplicated_ratings_are_merged() {
+        let row = |user: i64, item: i64, rating: f64, timestamp: i64| -> Row {
+            vec![Value::Integer(user), Value::Integer(item), Value::Float(rating), Value::Integer(timestamp)]
+        };
+        let rows = vec![row(1, 10, 4.0, 200), row(1, 11, 3.0, 100), row(1, 10, 2.0, 100)];
+
+        let mut report = ImportReport::default();
+        let latest = merge_duplicates(rows.clone(), DuplicateRatings::Latest, &mut report);
+        assert_eq!(latest, vec![row(1, 10, 4.0, 200), row(1, 11, 3.0, 100)]);
+        assert_eq!(report.total_dropped(), 1);
+
+        let average = merge_duplicates(rows, DuplicateRatings::Average, &mut report);
+        assert_eq!(average[0][2], Value::Float(3.0));
+    }
+}
@@ -0,0 +1,7 @@
+#[macro_use]
+extern crate diesel;
+
+pub mod mapping;
+pub mod importer;
+
+pub use importer::{import, dry_run};
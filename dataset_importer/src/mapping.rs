@@ -0,0 +1,126 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Declarative description of how a `user,item,rating` dataset maps to a database schema.
+///
+/// File paths are relative to the mapping file, so a mapping can live next to the data it describes.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DatasetMapping {
+    pub database_url: String,
+    /// Rows that can't be loaded are written here along with the reason.
+    pub reject_file: Option<String>,
+    pub users: EntityMapping,
+    pub items: EntityMapping,
+    pub ratings: RatingsMapping
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileFormat {
+    pub file: String,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    #[serde(default)]
+    pub has_headers: bool,
+    /// Any label known by the WHATWG Encoding Standard, e.g. `utf-8` or `latin1`.
+    #[serde(default = "default_encoding")]
+    pub encoding: String,
+    /// Character escaping quotes inside quoted fields, e.g. `\"` in Book-Crossing, instead of doubling them.
+    pub escape: Option<char>,
+    /// Values meaning missing data in every column of the file, stored as `NULL`.
+    #[serde(default)]
+    pub null_values: Vec<String>
+}
+
+/// A users or items source: one row per entity with its id and attribute columns.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EntityMapping {
+    pub table: String,
+    #[serde(flatten)]
+    pub format: FileFormat,
+    pub id: Column,
+    #[serde(default)]
+    pub attributes: Vec<Column>,
+    /// Rows repeating an id are expected (e.g. users taken from the ratings file) and merged silently.
+    #[serde(default)]
+    pub allow_duplicates: bool
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RatingsMapping {
+    pub table: String,
+    #[serde(flatten)]
+    pub format: FileFormat,
+    pub user: Column,
+    pub item: Column,
+    pub rating: Column,
+    pub timestamp: Option<Column>,
+    pub implicit: Option<ImplicitFeedback>,
+    /// How several ratings of the same user and item are merged into one.
+    #[serde(default)]
+    pub duplicates: DuplicateRatings
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateRatings {
+    /// Keeps the rating with the latest timestamp, or the last one of the file without a timestamp column.
+    #[default]
+    Latest,
+    /// Keeps the average of the ratings.
+    Average
+}
+
+/// Flags the ratings equal to `value` as implicit feedback in the boolean column `name`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImplicitFeedback {
+    pub value: f64,
+    pub name: String
+}
+
+/// Maps the column at position `column` of the file to the database column `name`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Column {
+    pub column: usize,
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: ColumnType,
+    /// Value written in the file for missing data, stored as `NULL`.
+    pub null: Option<String>,
+    /// Stores only this part of a free-text "city, state, country" location.
+    pub location: Option<LocationPart>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocationPart {
+    City,
+    State,
+    /// The country with its spellings normalized, e.g. "united states" and "usa".
+    Country
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Integer,
+    Float,
+    #[default]
+    Text
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_encoding() -> String {
+    String::from("utf-8")
+}
+
+impl DatasetMapping {
+    pub fn from_file(path: &Path) -> DatasetMapping {
+        let content = fs::read_to_string(path).unwrap_or_else(|error| panic!("Couldn't read the mapping file {}: {}", path.display(), error));
+        toml::from_str(&content).unwrap_or_else(|error| panic!("Invalid mapping file {}: {}", path.display(), error))
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde_json::json;
+
+use db_manager::{DBManager, User, Item};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// `userId,itemId,rating,timestamp` with a header, timestamps are left empty.
+    MovieLensCsv,
+    /// Sparse users by items coordinate matrix with 1-based indices.
+    MatrixMarket,
+    /// One rating per line with the user and item one-hot encoded, read by libFM and LibSVM tools.
+    LibFm,
+    /// One JSON object per user and item with its `data()` attributes.
+    JsonLines
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<ExportFormat> {
+        match name {
+            "movielens" => Some(ExportFormat::MovieLensCsv),
+            "mtx" => Some(ExportFormat::MatrixMarket),
+            "libfm" => Some(ExportFormat::LibFm),
+            "jsonl" => Some(ExportFormat::JsonLines),
+            _ => None
+        }
+    }
+}
+
+/// Assigns consecutive indices to ids in the order they are first seen.
+struct Index<ID> {
+    indices: HashMap<ID, usize>,
+    ids: Vec<ID>
+}
+
+impl<ID: Hash + Eq + Clone> Index<ID> {
+    fn create() -> Index<ID> {
+        Index{indices: HashMap::new(), ids: Vec::new()}
+    }
+
+    fn index_of(&mut self, id: &ID) -> usize {
+        if let Some(index) = self.indices.get(id) {
+            return *index;
+        }
+
+        self.ids.push(id.clone());
+        self.indices.insert(id.clone(), self.ids.len() - 1);
+        self.ids.len() - 1
+    }
+}
+
+/// Writes the explicit ratings, or the users and items for `JsonLines`, to `output`, reading `chunk_size`
+/// ratings or users per query. Matrix Market and libFM renumber users and items, the original
+/// ids of every index are written to `<output>.index.csv`. Returns the lines written.
+pub fn export<M, U, I>(manager: &M, format: ExportFormat, output: &Path, chunk_size: i64) -> usize
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    U::ID: Hash + Eq + Clone + Display,
+    I::ID: Hash + Eq + Clone + Display
+{
+    let mut writer = create_file(output);
+
+    let lines = match format {
+        ExportFormat::MovieLensCsv => {
+            writeln!(writer, "userId,itemId,rating,timestamp").expect("Failed to write the export");
+            let mut lines = 0;
+            for (user_id, item_id, rating) in manager.ratings_iter(chunk_size) {
+                writeln!(writer, "{},{},{},", csv_field(&user_id.to_string()), csv_field(&item_id.to_string()), rating).expect("Failed to write the export");
+                lines += 1;
+            }
+            lines
+        }
+        ExportFormat::MatrixMarket => export_matrix_market(manager, &mut writer, output, chunk_size),
+        ExportFormat::LibFm => export_libfm(manager, &mut writer, output, chunk_size),
+        ExportFormat::JsonLines => {
+            let users = for_each_user(manager, chunk_size, |user| {
+                let line = json!({"type": "user", "id": user.id().to_string(), "name": user.name(), "data": user.data(), "ratings": user.ratings().len()});
+                writeln!(writer, "{}", line).expect("Failed to write the export");
+                1
+            });
+
+            let mut items = 0;
+            let mut after = None;
+            loop {
+                let chunk = manager.get_items_chunk(after, chunk_size);
+                if chunk.is_empty() {
+                    break;
+                }
+
+                for item in &chunk {
+                    let line = json!({"type": "item", "id": item.id().to_string(), "name": item.name(), "data": item.data()});
+                    writeln!(writer, "{}", line).expect("Failed to write the export");
+                }
+                items += chunk.len();
+                after = chunk.last().map(|item| item.id());
+            }
+
+            users + items
+        }
+    };
+
+    writer.flush().expect("Failed to write the export");
+    lines
+}
+
+/// Coordinate entries go after a header with their count, so they are kept until every user is read.
+fn export_matrix_market<M, U, I>(manager: &M, writer: &mut BufWriter<File>, output: &Path, chunk_size: i64) -> usize
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    U::ID: Hash + Eq + Clone + Display,
+    I::ID: Hash + Eq + Clone + Display
+{
+    let mut users = Index::create();
+    let mut items = Index::create();
+    let mut entries = Vec::new();
+
+    for_each_user(manager, chunk_size, |user| {
+        let row = users.index_of(&user.id());
+        for (item_id, rating) in user.ratings() {
+            entries.push((row, items.index_of(&item_id), rating));
+        }
+        0
+    });
+
+    writeln!(writer, "%%MatrixMarket matrix coordinate real general").expect("Failed to write the export");
+    writeln!(writer, "{} {} {}", users.ids.len(), items.ids.len(), entries.len()).expect("Failed to write the export");
+    for (row, column, rating) in &entries {
+        writeln!(writer, "{} {} {}", row + 1, column + 1, rating).expect("Failed to write the export");
+    }
+
+    let mut index = create_file(&index_path(output));
+    writeln!(index, "kind,index,id").expect("Failed to write the index");
+    for (position, id) in users.ids.iter().enumerate() {
+        writeln!(index, "user,{},{}", position + 1, csv_field(&id.to_string())).expect("Failed to write the index");
+    }
+    for (position, id) in items.ids.iter().enumerate() {
+        writeln!(index, "item,{},{}", position + 1, csv_field(&id.to_string())).expect("Failed to write the index");
+    }
+    index.flush().expect("Failed to write the index");
+
+    entries.len()
+}
+
+/// Users and items share one feature space, numbered as they are found.
+fn export_libfm<M, U, I>(manager: &M, writer: &mut BufWriter<File>, output: &Path, chunk_size: i64) -> usize
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    U::ID: Hash + Eq + Clone + Display,
+    I::ID: Hash + Eq + Clone + Display
+{
+    let mut features: Index<String> = Index::create();
+
+    let mut lines = 0;
+    for (user_id, item_id, rating) in manager.ratings_iter(chunk_size) {
+        let user_feature = features.index_of(&format!("user,{}", csv_field(&user_id.to_string())));
+        let item_feature = features.index_of(&format!("item,{}", csv_field(&item_id.to_string())));
+        writeln!(writer, "{} {}:1 {}:1", rating, user_feature, item_feature).expect("Failed to write the export");
+        lines += 1;
+    }
+
+    let mut index = create_file(&index_path(output));
+    writeln!(index, "kind,index,id").expect("Failed to write the index");
+    for (position, feature) in features.ids.iter().enumerate() {
+        let (kind, id) = feature.split_at(feature.find(',').expect("Features are kind and id"));
+        writeln!(index, "{},{}{}", kind, position, id).expect("Failed to write the index");
+    }
+    index.flush().expect("Failed to write the index");
+
+    lines
+}
+
+/// Calls `f` with every user, ordered by id, and sums what it returns.
+fn for_each_user<M, U, I, F>(manager: &M, chunk_size: i64, mut f: F) -> usize
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    U::ID: Hash + Eq + Clone,
+    F: FnMut(&U) -> usize
+{
+    let mut total = 0;
+    let mut after = None;
+    loop {
+        let ids = manager.get_users_chunk(after, chunk_size);
+        if ids.is_empty() {
+            break;
+        }
+
+        let users = manager.get_users_by_ids(&ids);
+        for id in &ids {
+            // Users deleted since their id was read are left out
+            if let Some(user) = users.get(id) {
+                total += f(user);
+            }
+        }
+        after = ids.last().cloned();
+    }
+    total
+}
+
+fn create_file(path: &Path) -> BufWriter<File> {
+    let file = File::create(path).unwrap_or_else(|error| panic!("Couldn't create {}: {}", path.display(), error));
+    BufWriter::new(file)
+}
+
+fn index_path(output: &Path) -> std::path::PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".index.csv");
+    path.into()
+}
+
+/// Quotes a CSV field if it needs it, ISBNs and names can hold commas.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        String::from(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indices_follow_first_appearance() {
+        let mut index = Index::create();
+        assert_eq!(index.index_of(&"b"), 0);
+        assert_eq!(index.index_of(&"a"), 1);
+        assert_eq!(index.index_of(&"b"), 0);
+        assert_eq!(index.ids, vec!["b", "a"]);
+
+        assert_eq!(csv_field("0195153448"), "0195153448");
+        assert_eq!(csv_field("Room, The"), "\"Room, The\"");
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::sql_types::Text;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use db_manager::RatingScale;
+use dataset_importer::mapping::DatasetMapping;
+
+/// Attempts to find a new (user, item) pair before giving up on a rating, dense configurations
+/// end up with fewer ratings than asked instead of looping forever.
+const MAX_ATTEMPTS: usize = 100;
+
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    pub users: usize,
+    pub items: usize,
+    pub ratings: usize,
+    /// Zipf exponent of the item popularity, 0 makes every item as popular.
+    pub popularity_exponent: f64,
+    /// Zipf exponent of how many ratings each user gives.
+    pub activity_exponent: f64,
+    /// Dimensions of the latent user and item vectors the ratings come from.
+    pub factors: usize,
+    /// Standard deviation of the noise added to every rating, in rating units.
+    pub noise: f64,
+    pub scale: RatingScale,
+    pub seed: u64
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> GeneratorConfig {
+        GeneratorConfig{
+            users: 1000,
+            items: 2000,
+            ratings: 50000,
+            popularity_exponent: 1.0,
+            activity_exponent: 0.8,
+            factors: 8,
+            noise: 0.5,
+            scale: RatingScale::create(1.0, 5.0, Some(1.0)),
+            seed: 42
+        }
+    }
+}
+
+/// Ratings of users `1..=users` to items `1..=items`.
+#[derive(Debug, Clone)]
+pub struct SyntheticDataset {
+    pub users: usize,
+    pub items: usize,
+    pub ratings: Vec<(usize, usize, f64)>
+}
+
+/// Samples indices with probability proportional to `1/(index + 1)^exponent`.
+struct ZipfSampler {
+    cumulative: Vec<f64>
+}
+
+impl ZipfSampler {
+    fn create(n: usize, exponent: f64) -> ZipfSampler {
+        let mut total = 0.0;
+        let cumulative = (0..n)
+            .map(|rank| {
+                total += 1.0/((rank + 1) as f64).powf(exponent);
+                total
+            })
+            .collect();
+        ZipfSampler{cumulative}
+    }
+
+    fn sample(&self, rng: &mut ChaCha8Rng) -> usize {
+        let total = self.cumulative.last().copied().unwrap_or(0.0);
+        let target = rng.gen::<f64>()*total;
+        self.cumulative.partition_point(|value| *value < target).min(self.cumulative.len() - 1)
+    }
+}
+
+/// Generates the same dataset for the same configuration. Popular items and active users are the
+/// lowest ids, and ratings come from the dot product of random user and item vectors plus noise.
+pub fn generate(config: &GeneratorConfig) -> SyntheticDataset {
+    if config.users == 0 || config.items == 0 {
+        return SyntheticDataset{users: config.users, items: config.items, ratings: Vec::new()};
+    }
+
+    let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+
+    // Dot products of vectors with this deviation have unit deviation
+    let deviation = 1.0/(config.factors.max(1) as f64).powf(0.25);
+    let user_factors: Vec<Vec<f64>> = (0..config.users).map(|_| latent_vector(&mut rng, config.factors, deviation)).collect();
+    let item_factors: Vec<Vec<f64>> = (0..config.items).map(|_| latent_vector(&mut rng, config.factors, deviation)).collect();
+
+    let users = ZipfSampler::create(config.users, config.activity_exponent);
+    let items = ZipfSampler::create(config.items, config.popularity_exponent);
+
+    let scale = &config.scale;
+    let center = (scale.min + scale.max)/2.0;
+    let spread = (scale.max - scale.min)/4.0;
+
+    let mut rated = HashSet::new();
+    let mut ratings = Vec::with_capacity(config.ratings);
+    for _ in 0..config.ratings.min(config.users*config.items) {
+        let pair = (0..MAX_ATTEMPTS)
+            .map(|_| (users.sample(&mut rng), items.sample(&mut rng)))
+            .find(|pair| !rated.contains(pair));
+
+        if let Some((user, item)) = pair {
+            rated.insert((user, item));
+
+            let affinity: f64 = user_factors[user].iter().zip(&item_factors[item]).map(|(u, i)| u*i).sum();
+            let rating = center + spread*affinity + config.noise*normal(&mut rng);
+            ratings.push((user + 1, item + 1, snap(rating, scale)));
+        }
+    }
+
+    SyntheticDataset{users: config.users, items: config.items, ratings}
+}
+
+/// Writes `users.csv`, `items.csv`, `ratings.csv` and an `import.toml` loading them with the
+/// dataset importer into a database with the MovieLens schema at `database_url`.
+pub fn write_csv(dataset: &SyntheticDataset, dir: &Path, database_url: &str) {
+    fs::create_dir_all(dir).unwrap_or_else(|error| panic!("Couldn't create {}: {}", dir.display(), error));
+
+    let mut users = create_file(&dir.join("users.csv"));
+    writeln!(users, "userId").expect("Failed to write the users");
+    for user in 1..=dataset.users {
+        writeln!(users, "{}", user).expect("Failed to write the users");
+    }
+
+    let mut items = create_file(&dir.join("items.csv"));
+    writeln!(items, "itemId,title,genres").expect("Failed to write the items");
+    for item in 1..=dataset.items {
+        writeln!(items, "{},Synthetic item {},(no genres listed)", item, item).expect("Failed to write the items");
+    }
+
+    let mut ratings = create_file(&dir.join("ratings.csv"));
+    writeln!(ratings, "userId,itemId,rating").expect("Failed to write the ratings");
+    for (user, item, rating) in &dataset.ratings {
+        writeln!(ratings, "{},{},{}", user, item, rating).expect("Failed to write the ratings");
+    }
+
+    let mapping = format!(r#"# Synthetic dataset, loaded with `cargo run -p dataset_importer --bin import -- import.toml`
+database_url = "{database_url}"
+
+[users]
+table = "users"
+file = "users.csv"
+has_headers = true
+id = {{ column = 0, name = "id", type = "integer" }}
+
+[items]
+table = "movies"
+file = "items.csv"
+has_headers = true
+id = {{ column = 0, name = "id", type = "integer" }}
+attributes = [
+    {{ column = 1, name = "title", type = "text" }},
+    {{ column = 2, name = "genres", type = "text" }},
+]
+
+[ratings]
+table = "ratings"
+file = "ratings.csv"
+has_headers = true
+user = {{ column = 0, name = "user_id", type = "integer" }}
+item = {{ column = 1, name = "movie_id", type = "integer" }}
+rating = {{ column = 2, name = "rating", type = "float" }}
+"#);
+    fs::write(dir.join("import.toml"), mapping).expect("Failed to write the import mapping");
+}
+
+#[derive(QueryableByName)]
+struct ColumnName {
+    #[sql_type = "Text"]
+    column_name: String
+}
+
+/// Tables and columns of the mapping `write_csv` writes, which is the MovieLens schema of
+/// small_movielens, that the database of the mapping is missing as `table.column`.
+pub fn missing_columns(mapping: &DatasetMapping) -> Vec<String> {
+    let connector = PgConnection::establish(&mapping.database_url).expect("Failed connection to database. Maybe the URL?");
+    let tables = [
+        (&mapping.users.table, vec![&mapping.users.id]),
+        (&mapping.items.table, std::iter::once(&mapping.items.id).chain(&mapping.items.attributes).collect()),
+        (&mapping.ratings.table, vec![&mapping.ratings.user, &mapping.ratings.item, &mapping.ratings.rating])
+    ];
+
+    let mut missing = Vec::new();
+    for (table, columns) in tables {
+        let existing: HashSet<String> = diesel::sql_query("SELECT column_name::TEXT FROM information_schema.columns WHERE table_schema = current_schema() AND table_name = $1")
+            .bind::<Text, _>(table)
+            .load::<ColumnName>(&connector)
+            .expect("Failed query of the database columns")
+            .into_iter()
+            .map(|column| column.column_name)
+            .collect();
+
+        missing.extend(columns.iter()
+            .filter(|column| !existing.contains(&column.name))
+            .map(|column| format!("{}.{}", table, column.name)));
+    }
+    missing
+}
+
+fn latent_vector(rng: &mut ChaCha8Rng, factors: usize, deviation: f64) -> Vec<f64> {
+    (0..factors).map(|_| deviation*normal(rng)).collect()
+}
+
+/// Standard normal sample by the Box-Muller transform.
+fn normal(rng: &mut ChaCha8Rng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0*u1.ln()).sqrt()*(2.0*std::f64::consts::PI*u2).cos()
+}
+
+/// Rounds to the closest valid rating of the scale.
+fn snap(rating: f64, scale: &RatingScale) -> f64 {
+    let rating = scale.clamp(rating);
+    match scale.step {
+        Some(step) => scale.clamp(scale.min + ((rating - scale.min)/step).round()*step),
+        None => rating
+    }
+}
+
+fn create_file(path: &Path) -> std::io::BufWriter<fs::File> {
+    let file = fs::File::create(path).unwrap_or_else(|error| panic!("Couldn't create {}: {}", path.display(), error));
+    std::io::BufWriter::new(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_datasets() {
+        let config = GeneratorConfig{users: 50, items: 100, ratings: 1000, ..Default::default()};
+        let dataset = generate(&config);
+
+        assert_eq!(dataset.ratings.len(), 1000);
+        assert_eq!(generate(&config).ratings, dataset.ratings);
+        assert!(dataset.ratings.iter().all(|(_, _, rating)| config.scale.contains(*rating)));
+
+        let pairs: HashSet<(usize, usize)> = dataset.ratings.iter().map(|(user, item, _)| (*user, *item)).collect();
+        assert_eq!(pairs.len(), dataset.ratings.len());
+
+        let ratings_of = |item: usize| dataset.ratings.iter().filter(|rating| rating.1 == item).count();
+        assert!(ratings_of(1) > ratings_of(100));
+    }
+}
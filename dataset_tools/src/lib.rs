@@ -0,0 +1,8 @@
+#[macro_use]
+extern crate diesel;
+
+pub mod export;
+pub mod generate;
+pub mod registry;
+pub mod stats;
+pub mod validate;
@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use db_manager::{DBManager, User, Item, InstrumentedManager, RatingScale};
+use dataset_importer::mapping::DatasetMapping;
+
+use dataset_tools::{export::{self, ExportFormat}, generate::{self, GeneratorConfig}, registry, stats, validate::{self, DatasetLayout}};
+
+const USAGE: &str = "Usage:
+  dataset_tools stats <dataset> [--json] [--chunk-size N] [--profile] [--config FILE]
+  dataset_tools validate <dataset> [--config FILE]
+  dataset_tools export <dataset> <movielens|mtx|libfm|jsonl> <output file> [--chunk-size N] [--profile] [--config FILE]
+  dataset_tools generate <output dir> [--users N] [--items N] [--ratings N] [--seed N] [--database-url URL] [--load]
+--load imports the generated dataset into --database-url, which must have the MovieLens schema of small_movielens
+--profile prints the database calls made, with their times and rows, to stderr at the end
+--config is the datasets.toml giving where the datasets are, the one in the working directory by default";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        exit_with_usage();
+    }
+
+    let chunk_size = option(&args, "--chunk-size").unwrap_or(1000);
+    if chunk_size <= 0 {
+        exit_with_usage();
+    }
+    let profile = args.iter().any(|arg| arg == "--profile");
+    let config = registry::config_path(&args);
+
+    match (args[0].as_str(), args[1].as_str()) {
+        ("stats", dataset) => {
+            let json = args.iter().any(|arg| arg == "--json");
+            let manager = registry::datasets(&config).connect(dataset).unwrap_or_else(|| exit_with_usage());
+            print_stats(manager, chunk_size, json, profile);
+        }
+        ("validate", dataset) => {
+            let datasets = registry::datasets(&config);
+            let manager = datasets.connect(dataset).unwrap_or_else(|| exit_with_usage());
+            let url = datasets.url(dataset).expect("The dataset is registered");
+            let dataset_config = &registry::configs(&config)[dataset];
+            let passed = match (dataset_config.mapping_path(), dataset_config.matrix_path()) {
+                (Some(mapping_path), _) => validate_mapped(dataset, &mapping_path, &manager.rating_scale()),
+                (None, Some(matrix_path)) => validate_matrix(dataset, url, &matrix_path, &manager.rating_scale()),
+                (None, None) => {
+                    eprintln!("{} needs a mapping or a matrix in {} to be validated", dataset, config.display());
+                    process::exit(1);
+                }
+            };
+
+            if !passed {
+                process::exit(2);
+            }
+        }
+        ("export", dataset) => {
+            if args.len() < 4 {
+                exit_with_usage();
+            }
+            let format = ExportFormat::from_name(&args[2]).unwrap_or_else(|| exit_with_usage());
+            let output = Path::new(&args[3]);
+            let manager = registry::datasets(&config).connect(dataset).unwrap_or_else(|| exit_with_usage());
+            export_dataset(manager, format, output, chunk_size, profile);
+        }
+        ("generate", output) => {
+            let defaults = GeneratorConfig::default();
+            let config = GeneratorConfig{
+                users: option(&args, "--users").unwrap_or(defaults.users),
+                items: option(&args, "--items").unwrap_or(defaults.items),
+                ratings: option(&args, "--ratings").unwrap_or(defaults.ratings),
+                seed: option(&args, "--seed").unwrap_or(defaults.seed),
+                ..defaults
+            };
+            let database_url: String = option(&args, "--database-url").unwrap_or_else(|| String::from("postgres://ademir:@localhost/synthetic"));
+
+            let dataset = generate::generate(&config);
+            let output = Path::new(output);
+            generate::write_csv(&dataset, output, &database_url);
+            println!("Generated {} ratings of {} users to {} items in {}", dataset.ratings.len(), dataset.users, dataset.items, output.display());
+
+            if args.iter().any(|arg| arg == "--load") {
+                let mapping_path = output.join("import.toml");
+                let missing = generate::missing_columns(&DatasetMapping::from_file(&mapping_path));
+                if !missing.is_empty() {
+                    eprintln!("--load needs the MovieLens schema of small_movielens, {} is missing {}", database_url, missing.join(", "));
+                    process::exit(1);
+                }
+                println!("{}", dataset_importer::import(&mapping_path, true));
+            }
+        }
+        _ => exit_with_usage()
+    }
+}
+
+/// Value following the flag `name`, exits with the usage if it's there but not valid.
+fn option<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+    let position = args.iter().position(|arg| arg == name)?;
+    let value = args.get(position + 1).and_then(|value| value.parse().ok());
+    Some(value.unwrap_or_else(|| exit_with_usage()))
+}
+
+fn print_stats<M, U, I>(manager: M, chunk_size: i64, json: bool, profile: bool)
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    U::ID: Hash + Eq + Clone,
+    I::ID: Hash + Eq + Clone
+{
+    let (stats, report) = if profile {
+        let manager = InstrumentedManager::create(manager);
+        (stats::compute_stats(&manager, chunk_size), Some(manager.report()))
+    } else {
+        (stats::compute_stats(&manager, chunk_size), None)
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats).expect("Failed to serialize the statistics"));
+    } else {
+        print!("{}", stats);
+    }
+    if let Some(report) = report {
+        eprint!("{}", report);
+    }
+}
+
+fn export_dataset<M, U, I>(manager: M, format: ExportFormat, output: &Path, chunk_size: i64, profile: bool)
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    U::ID: Hash + Eq + Clone + Display,
+    I::ID: Hash + Eq + Clone + Display
+{
+    let (lines, report) = if profile {
+        let manager = InstrumentedManager::create(manager);
+        (export::export(&manager, format, output, chunk_size), Some(manager.report()))
+    } else {
+        (export::export(&manager, format, output, chunk_size), None)
+    };
+
+    println!("Exported {} lines to {}", lines, output.display());
+    if let Some(report) = report {
+        eprint!("{}", report);
+    }
+}
+
+/// Validates a dataset loaded by the importer, the rows expected are the source records it didn't reject.
+fn validate_mapped(dataset: &str, mapping_path: &Path, scale: &RatingScale) -> bool {
+    let mapping = DatasetMapping::from_file(mapping_path);
+
+    let base_dir = mapping_path.parent().unwrap_or_else(|| Path::new("."));
+    let expected_rows = validate::source_rows(&mapping, base_dir);
+    let connector = PgConnection::establish(&mapping.database_url).expect("Failed connection to database. Maybe the URL?");
+
+    let report = validate::validate(dataset, &connector, &DatasetLayout::from_mapping(&mapping), scale, &expected_rows);
+    print!("{}", report);
+    report.passed()
+}
+
+/// Datasets without mapping are the simple movies, a matrix of movies by users where every filled cell is a rating.
+fn validate_matrix(dataset: &str, url: &str, matrix_path: &Path, scale: &RatingScale) -> bool {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_path(matrix_path)
+        .unwrap_or_else(|error| panic!("Couldn't load from csv file {}: {}", matrix_path.display(), error));
+
+    let mut expected_rows = BTreeMap::new();
+    for (row, record) in reader.records().enumerate() {
+        let record = record.expect("Failed to read the ratings matrix");
+        if row == 0 {
+            expected_rows.insert(String::from("users"), record.len() - 1);
+            expected_rows.insert(String::from("movies"), 0);
+            expected_rows.insert(String::from("ratings"), 0);
+            continue;
+        }
+
+        *expected_rows.get_mut("movies").unwrap() += 1;
+        *expected_rows.get_mut("ratings").unwrap() += record.iter().skip(1).filter(|cell| !cell.is_empty()).count();
+    }
+
+    let layout = DatasetLayout{
+        users_table: String::from("users"),
+        user_id: String::from("id"),
+        items_table: String::from("movies"),
+        item_id: String::from("id"),
+        ratings_table: String::from("ratings"),
+        rating_user: String::from("user_id"),
+        rating_item: String::from("movie_id"),
+        rating_value: String::from("rating"),
+        implicit: None
+    };
+    let connector = PgConnection::establish(url).expect("Failed connection to database. Maybe the URL?");
+
+    let report = validate::validate(dataset, &connector, &layout, scale, &expected_rows);
+    print!("{}", report);
+    report.passed()
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    let config = registry::config_path(&env::args().collect::<Vec<_>>());
+    if config.exists() {
+        eprintln!("Datasets: {}", registry::configs(&config).keys().cloned().collect::<Vec<_>>().join(", "));
+    }
+    process::exit(1);
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use db_manager::Registry;
+use dataset_importer::mapping::DatasetMapping;
+use simple_movie_db_manager::{movie_db_manager::MovieDBManager, movie_user::MovieUser, movie_item::MovieItem};
+use book_db_manager::{book_db_manager::BookDBManager, book_user::BookUser, book_item::BookItem};
+use small_movielens_db_manager::{small_movielens_db_manager::SmallMovielensDBManager, movie_user::SMovieLensUser, movie_item::SMovieLensItem};
+
+/// Datasets file read when no `--config` is given, relative to the working directory.
+pub const DEFAULT_CONFIG: &str = "datasets.toml";
+
+/// Where a dataset is stored, as given in `datasets.toml`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DatasetConfig {
+    /// Mapping the dataset is loaded with, relative to `datasets.toml`.
+    pub mapping: Option<String>,
+    /// Database of a dataset without mapping.
+    pub database_url: Option<String>,
+    /// Ratings matrix a dataset without mapping is loaded from, relative to `datasets.toml`.
+    pub matrix: Option<String>,
+    #[serde(skip)]
+    base_dir: PathBuf
+}
+
+impl DatasetConfig {
+    pub fn mapping_path(&self) -> Option<PathBuf> {
+        self.mapping.as_ref().map(|mapping| self.base_dir.join(mapping))
+    }
+
+    pub fn matrix_path(&self) -> Option<PathBuf> {
+        self.matrix.as_ref().map(|matrix| self.base_dir.join(matrix))
+    }
+
+    pub fn database_url(&self) -> String {
+        match (&self.database_url, self.mapping_path()) {
+            (Some(url), _) => url.clone(),
+            (None, Some(mapping)) => DatasetMapping::from_file(&mapping).database_url,
+            (None, None) => panic!("Datasets need a mapping or a database_url in datasets.toml")
+        }
+    }
+}
+
+/// Path following `--config` in the arguments, or `DEFAULT_CONFIG`.
+pub fn config_path(args: &[String]) -> PathBuf {
+    let position = args.iter().position(|arg| arg == "--config");
+    let path = position.and_then(|position| args.get(position + 1)).map(String::as_str);
+    PathBuf::from(path.unwrap_or(DEFAULT_CONFIG))
+}
+
+/// The datasets of the `datasets.toml` at `path` by name.
+pub fn configs(path: &Path) -> BTreeMap<String, DatasetConfig> {
+    let content = fs::read_to_string(path).unwrap_or_else(|error| panic!("Couldn't read {}: {}", path.display(), error));
+    let mut configs: BTreeMap<String, DatasetConfig> = toml::from_str(&content).unwrap_or_else(|error| panic!("Invalid {}: {}", path.display(), error));
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    for config in configs.values_mut() {
+        config.base_dir = base_dir.to_path_buf();
+    }
+    configs
+}
+
+/// Every dataset the system knows, at the databases the `datasets.toml` at `path` gives.
+pub fn datasets(path: &Path) -> Registry {
+    let configs = configs(path);
+    let url = |name: &str| configs.get(name)
+        .unwrap_or_else(|| panic!("The dataset {} is missing in datasets.toml", name))
+        .database_url();
+
+    let mut registry = Registry::create();
+    registry.register::<MovieDBManager, MovieUser, MovieItem>("simple_movies", &url("simple_movies"));
+    registry.register::<BookDBManager, BookUser, BookItem>("books", &url("books"));
+    registry.register::<SmallMovielensDBManager, SMovieLensUser, SMovieLensItem>("small_movielens", &url("small_movielens"));
+    registry
+}
@@ -0,0 +1,271 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::hash::Hash;
+
+use serde::Serialize;
+
+use db_manager::{DBManager, User, Item};
+
+/// Shares of the most popular items at which the long-tail curve is sampled.
+const LONG_TAIL_POINTS: [f64; 7] = [0.01, 0.05, 0.1, 0.2, 0.5, 0.8, 1.0];
+
+/// Profile of the explicit ratings of a dataset.
+#[derive(Debug, Clone, Serialize)]
+pub struct DatasetStats {
+    pub users: usize,
+    pub users_with_ratings: usize,
+    /// Items in the catalog, with or without ratings.
+    pub items: usize,
+    /// Items with at least one rating.
+    pub rated_items: usize,
+    pub ratings: usize,
+    /// Share of the users by items matrix that has a rating.
+    pub density: f64,
+    pub rating_mean: f64,
+    pub rating_std_dev: f64,
+    pub rating_distribution: Vec<RatingCount>,
+    pub ratings_per_user: CountSummary,
+    pub ratings_per_item: CountSummary,
+    pub long_tail: Vec<LongTailPoint>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RatingCount {
+    pub rating: f64,
+    pub count: usize
+}
+
+/// Distribution of how many ratings each user, or item, has.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CountSummary {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// Power of two buckets, the first one holds the ones without ratings.
+    pub histogram: Vec<Bucket>
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Bucket {
+    pub from: usize,
+    pub to: usize,
+    pub count: usize
+}
+
+/// Share of the ratings received by the given share of the most popular items.
+#[derive(Debug, Clone, Serialize)]
+pub struct LongTailPoint {
+    pub items_share: f64,
+    pub ratings_share: f64
+}
+
+/// Accumulates the ratings of a dataset one user at a time.
+pub struct StatsAccumulator<IID> {
+    ratings_per_user: Vec<usize>,
+    ratings_per_item: HashMap<IID, usize>,
+    /// Ratings in thousandths, so they can be ordered and counted.
+    distribution: BTreeMap<i64, usize>,
+    sum: f64,
+    sum_squared: f64
+}
+
+impl<IID: Hash + Eq + Clone> StatsAccumulator<IID> {
+    pub fn create() -> StatsAccumulator<IID> {
+        StatsAccumulator{
+            ratings_per_user: Vec::new(),
+            ratings_per_item: HashMap::new(),
+            distribution: BTreeMap::new(),
+            sum: 0.0,
+            sum_squared: 0.0
+        }
+    }
+
+    pub fn add_user(&mut self, ratings: &HashMap<IID, f64>) {
+        self.ratings_per_user.push(ratings.len());
+
+        for (item, rating) in ratings {
+            *self.ratings_per_item.entry(item.clone()).or_insert(0) += 1;
+            *self.distribution.entry((rating*1000.0).round() as i64).or_insert(0) += 1;
+            self.sum += rating;
+            self.sum_squared += rating.powi(2);
+        }
+    }
+
+    /// `items` is the size of the catalog, the items nobody rated count as items without ratings.
+    pub fn finish(self, items: usize) -> DatasetStats {
+        let ratings: usize = self.ratings_per_user.iter().sum();
+        let users = self.ratings_per_user.len();
+        let rated_items = self.ratings_per_item.len();
+        let items = items.max(rated_items);
+
+        let rating_mean = if ratings > 0 { self.sum/ratings as f64 } else { 0.0 };
+        let variance = if ratings > 0 { self.sum_squared/ratings as f64 - rating_mean.powi(2) } else { 0.0 };
+
+        let mut item_counts: Vec<usize> = self.ratings_per_item.values().copied().collect();
+        item_counts.resize(items, 0);
+        item_counts.sort_unstable_by(|a, b| b.cmp(a));
+
+        DatasetStats{
+            users,
+            users_with_ratings: self.ratings_per_user.iter().filter(|count| **count > 0).count(),
+            items,
+            rated_items,
+            ratings,
+            density: if users*items > 0 { ratings as f64/(users*items) as f64 } else { 0.0 },
+            rating_mean,
+            rating_std_dev: variance.max(0.0).sqrt(),
+            rating_distribution: self.distribution.iter()
+                .map(|(rating, count)| RatingCount{rating: *rating as f64/1000.0, count: *count})
+                .collect(),
+            ratings_per_user: summarize(self.ratings_per_user),
+            long_tail: long_tail(&item_counts, ratings),
+            ratings_per_item: summarize(item_counts)
+        }
+    }
+}
+
+/// Computes the statistics reading the users in chunks of `chunk_size`, so the whole dataset is never in memory.
+pub fn compute_stats<M, U, I>(manager: &M, chunk_size: i64) -> DatasetStats
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    U::ID: Hash + Eq + Clone,
+    I::ID: Hash + Eq + Clone
+{
+    let mut accumulator = StatsAccumulator::create();
+    let no_ratings = HashMap::new();
+
+    let mut after = None;
+    loop {
+        let users = manager.get_users_chunk(after.clone(), chunk_size);
+        if users.is_empty() {
+            break;
+        }
+
+        let ratings = manager.get_users_with_ratings_chunk(after, chunk_size);
+        for user in &users {
+            accumulator.add_user(ratings.get(user).unwrap_or(&no_ratings));
+        }
+
+        after = users.last().cloned();
+    }
+
+    accumulator.finish(manager.count_items() as usize)
+}
+
+fn summarize(mut counts: Vec<usize>) -> CountSummary {
+    if counts.is_empty() {
+        return CountSummary::default();
+    }
+
+    counts.sort_unstable();
+    let n = counts.len();
+    let median = if n.is_multiple_of(2) { (counts[n/2 - 1] + counts[n/2]) as f64/2.0 } else { counts[n/2] as f64 };
+
+    let mut histogram = vec![Bucket{from: 0, to: 0, count: 0}];
+    for count in &counts {
+        if *count == 0 {
+            histogram[0].count += 1;
+            continue;
+        }
+
+        let bucket = (usize::BITS - count.leading_zeros()) as usize;
+        while histogram.len() <= bucket {
+            let from = 1 << (histogram.len() - 1);
+            histogram.push(Bucket{from, to: 2*from - 1, count: 0});
+        }
+        histogram[bucket].count += 1;
+    }
+
+    CountSummary{
+        min: counts[0],
+        max: counts[n - 1],
+        mean: counts.iter().sum::<usize>() as f64/n as f64,
+        median,
+        histogram
+    }
+}
+
+/// `item_counts` sorted from the most rated item.
+fn long_tail(item_counts: &[usize], ratings: usize) -> Vec<LongTailPoint> {
+    if ratings == 0 {
+        return Vec::new();
+    }
+
+    LONG_TAIL_POINTS.iter()
+        .map(|items_share| {
+            let items = ((items_share*item_counts.len() as f64).ceil() as usize).min(item_counts.len());
+            let head_ratings: usize = item_counts[..items].iter().sum();
+            LongTailPoint{items_share: *items_share, ratings_share: head_ratings as f64/ratings as f64}
+        })
+        .collect()
+}
+
+impl Display for DatasetStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Users: {} ({} with ratings)", self.users, self.users_with_ratings)?;
+        writeln!(f, "Items: {} ({} with ratings)", self.items, self.rated_items)?;
+        writeln!(f, "Ratings: {}", self.ratings)?;
+        writeln!(f, "Density: {:.6} (sparsity {:.6})", self.density, 1.0 - self.density)?;
+        writeln!(f, "Rating mean: {:.3}, std dev: {:.3}", self.rating_mean, self.rating_std_dev)?;
+
+        writeln!(f, "Rating distribution:")?;
+        for rating_count in &self.rating_distribution {
+            writeln!(f, "  {:>6}: {}", rating_count.rating, rating_count.count)?;
+        }
+
+        write!(f, "Ratings per user:\n{}", self.ratings_per_user)?;
+        write!(f, "Ratings per item:\n{}", self.ratings_per_item)?;
+
+        writeln!(f, "Long tail:")?;
+        for point in &self.long_tail {
+            writeln!(f, "  top {:>5.1}% of items: {:.1}% of ratings", point.items_share*100.0, point.ratings_share*100.0)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for CountSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "  min {}, max {}, mean {:.2}, median {}", self.min, self.max, self.mean, self.median)?;
+        for bucket in &self.histogram {
+            if bucket.from == bucket.to {
+                writeln!(f, "  {:>13}: {}", bucket.from, bucket.count)?;
+            } else {
+                writeln!(f, "  {:>13}: {}", format!("{}-{}", bucket.from, bucket.to), bucket.count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulated_stats() {
+        let mut accumulator = StatsAccumulator::create();
+        accumulator.add_user(&vec![(1, 4.0), (2, 2.0)].into_iter().collect());
+        accumulator.add_user(&vec![(1, 5.0), (2, 3.0), (3, 1.0)].into_iter().collect());
+        accumulator.add_user(&HashMap::new());
+
+        let stats = accumulator.finish(4);
+        assert_eq!((stats.users, stats.users_with_ratings, stats.items, stats.rated_items, stats.ratings), (3, 2, 4, 3, 5));
+        assert!((stats.density - 5.0/12.0).abs() < 1e-9);
+        assert_eq!(stats.rating_mean, 3.0);
+        assert_eq!(stats.rating_distribution.len(), 5);
+
+        assert_eq!(stats.ratings_per_user.median, 2.0);
+        let histogram: Vec<(usize, usize, usize)> = stats.ratings_per_user.histogram.iter().map(|bucket| (bucket.from, bucket.to, bucket.count)).collect();
+        assert_eq!(histogram, vec![(0, 0, 1), (1, 1, 0), (2, 3, 2)]);
+
+        // The item nobody rated is in the zero bucket
+        assert_eq!(stats.ratings_per_item.histogram[0].count, 1);
+        assert_eq!(stats.ratings_per_item.min, 0);
+
+        assert_eq!(stats.long_tail.last().unwrap().ratings_share, 1.0);
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Display};
+use std::path::Path;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+use diesel::sql_types::BigInt;
+use serde::Deserialize;
+
+use db_manager::RatingScale;
+use dataset_importer::mapping::{DatasetMapping, FileFormat};
+
+/// Tables and columns holding a dataset.
+#[derive(Debug, Clone)]
+pub struct DatasetLayout {
+    pub users_table: String,
+    pub user_id: String,
+    pub items_table: String,
+    pub item_id: String,
+    pub ratings_table: String,
+    pub rating_user: String,
+    pub rating_item: String,
+    pub rating_value: String,
+    /// Boolean column flagging implicit feedback, left out of the scale check.
+    pub implicit: Option<String>
+}
+
+impl DatasetLayout {
+    pub fn from_mapping(mapping: &DatasetMapping) -> DatasetLayout {
+        DatasetLayout{
+            users_table: mapping.users.table.clone(),
+            user_id: mapping.users.id.name.clone(),
+            items_table: mapping.items.table.clone(),
+            item_id: mapping.items.id.name.clone(),
+            ratings_table: mapping.ratings.table.clone(),
+            rating_user: mapping.ratings.user.name.clone(),
+            rating_item: mapping.ratings.item.name.clone(),
+            rating_value: mapping.ratings.rating.name.clone(),
+            implicit: mapping.ratings.implicit.as_ref().map(|implicit| implicit.name.clone())
+        }
+    }
+}
+
+/// A row of the reject file written by the importer.
+#[derive(Debug, Deserialize)]
+struct Reject {
+    table: String,
+    file: String,
+    line: Option<u64>
+}
+
+/// Rows every table should have after the mapping is loaded, counted from the source files apart
+/// from the importer: the distinct keys of the records that aren't in the reject file of the load.
+pub fn source_rows(mapping: &DatasetMapping, base_dir: &Path) -> BTreeMap<String, usize> {
+    let reject_file = mapping.reject_file.as_ref()
+        .unwrap_or_else(|| panic!("The mapping needs a reject_file to know the rows left out"));
+    let rejected = rejected_lines(&base_dir.join(reject_file));
+
+    let mut rows = BTreeMap::new();
+    rows.insert(mapping.users.table.clone(), distinct_keys(&mapping.users.table, &mapping.users.format, &[mapping.users.id.column], base_dir, &rejected));
+    rows.insert(mapping.items.table.clone(), distinct_keys(&mapping.items.table, &mapping.items.format, &[mapping.items.id.column], base_dir, &rejected));
+    let ratings_key = [mapping.ratings.user.column, mapping.ratings.item.column];
+    rows.insert(mapping.ratings.table.clone(), distinct_keys(&mapping.ratings.table, &mapping.ratings.format, &ratings_key, base_dir, &rejected));
+    rows
+}
+
+/// Table, file and line of every rejected record.
+fn rejected_lines(path: &Path) -> HashSet<(String, String, u64)> {
+    let mut reader = csv::Reader::from_path(path)
+        .unwrap_or_else(|error| panic!("Couldn't read the reject file {}, was the dataset imported? {}", path.display(), error));
+
+    reader.deserialize::<Reject>()
+        .map(|reject| reject.unwrap_or_else(|error| panic!("Invalid reject file {}, import the dataset again: {}", path.display(), error)))
+        .filter_map(|reject| Some((reject.table, reject.file, reject.line?)))
+        .collect()
+}
+
+/// Distinct values of the key columns in the records of the file not rejected for `table`.
+fn distinct_keys(table: &str, format: &FileFormat, key_columns: &[usize], base_dir: &Path, rejected: &HashSet<(String, String, u64)>) -> usize {
+    let path = base_dir.join(&format.file);
+    let escape = format.escape.map(|escape| escape as u8);
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(format.has_headers)
+        .delimiter(format.delimiter as u8)
+        .escape(escape)
+        .double_quote(escape.is_none())
+        .flexible(true)
+        .from_path(&path)
+        .unwrap_or_else(|error| panic!("Couldn't load from csv file {}: {}", path.display(), error));
+
+    let mut keys = HashSet::new();
+    for record in reader.byte_records().flatten() {
+        let line = record.position().map(|position| position.line());
+        if line.is_some_and(|line| rejected.contains(&(String::from(table), format.file.clone(), line))) {
+            continue;
+        }
+
+        let key: Vec<Vec<u8>> = key_columns.iter()
+            .map(|column| record.get(*column).unwrap_or_default().trim_ascii().to_vec())
+            .collect();
+        keys.insert(key);
+    }
+    keys.len()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// The data is wrong, the dataset fails the validation.
+    Error,
+    /// Worth a look but valid, e.g. users who never rated anything.
+    Warning
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub severity: Severity,
+    pub problems: usize,
+    pub details: Vec<String>
+}
+
+impl Check {
+    fn create(name: &str, severity: Severity, problems: usize) -> Check {
+        Check{name: String::from(name), severity, problems, details: Vec::new()}
+    }
+
+    pub fn passed(&self) -> bool {
+        self.problems == 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationReport {
+    pub dataset: String,
+    pub checks: Vec<Check>
+}
+
+impl ValidationReport {
+    /// Warnings don't fail the validation.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed() || check.severity == Severity::Warning)
+    }
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[sql_type = "BigInt"]
+    count: i64
+}
+
+/// Checks the loaded data: orphan, out of scale and duplicated ratings, users and items without
+/// ratings, and that every table has the rows expected from the source files.
+pub fn validate(dataset: &str, connector: &PgConnection, layout: &DatasetLayout, scale: &RatingScale, expected_rows: &BTreeMap<String, usize>) -> ValidationReport {
+    let ratings = identifier(&layout.ratings_table);
+    let users = identifier(&layout.users_table);
+    let items = identifier(&layout.items_table);
+    let rating_user = format!("r.{}", identifier(&layout.rating_user));
+    let rating_item = format!("r.{}", identifier(&layout.rating_item));
+    let rating_value = format!("r.{}", identifier(&layout.rating_value));
+    let user_id = identifier(&layout.user_id);
+    let item_id = identifier(&layout.item_id);
+
+    let mut checks = Vec::new();
+
+    let orphans = count(connector, &format!(
+        "SELECT COUNT(*) AS count FROM {ratings} r
+            WHERE NOT EXISTS (SELECT 1 FROM {users} u WHERE u.{user_id} = {rating_user})
+                OR NOT EXISTS (SELECT 1 FROM {items} i WHERE i.{item_id} = {rating_item})"
+    ));
+    checks.push(Check::create("orphan ratings", Severity::Error, orphans));
+
+    let mut out_of_scale = format!("{rating_value} < {} OR {rating_value} > {}", scale.min, scale.max);
+    if let Some(step) = scale.step {
+        let steps = format!("({rating_value} - {})/{}", scale.min, step);
+        out_of_scale = format!("{} OR ABS({steps} - ROUND({steps})) > 1e-9", out_of_scale);
+    }
+    let explicit = match &layout.implicit {
+        Some(implicit) => format!("NOT r.{} AND", identifier(implicit)),
+        None => String::new()
+    };
+    let mut scale_check = Check::create("ratings out of scale", Severity::Error, count(connector, &format!(
+        "SELECT COUNT(*) AS count FROM {ratings} r WHERE {explicit} ({out_of_scale})"
+    )));
+    scale_check.details.push(format!("scale from {} to {}{}", scale.min, scale.max, scale.step.map(|step| format!(" in steps of {}", step)).unwrap_or_default()));
+    checks.push(scale_check);
+
+    let duplicates = count(connector, &format!(
+        "SELECT COUNT(*) AS count FROM (SELECT 1 FROM {ratings} r GROUP BY {rating_user}, {rating_item} HAVING COUNT(*) > 1) duplicated"
+    ));
+    checks.push(Check::create("duplicated (user, item) ratings", Severity::Error, duplicates));
+
+    let unrated_users = count(connector, &format!(
+        "SELECT COUNT(*) AS count FROM {users} u WHERE NOT EXISTS (SELECT 1 FROM {ratings} r WHERE {rating_user} = u.{user_id})"
+    ));
+    checks.push(Check::create("users without ratings", Severity::Warning, unrated_users));
+
+    let unrated_items = count(connector, &format!(
+        "SELECT COUNT(*) AS count FROM {items} i WHERE NOT EXISTS (SELECT 1 FROM {ratings} r WHERE {rating_item} = i.{item_id})"
+    ));
+    checks.push(Check::create("items without ratings", Severity::Warning, unrated_items));
+
+    let mut rows_check = Check::create("row counts against the source files", Severity::Error, 0);
+    for (table, expected) in expected_rows {
+        let rows = count(connector, &format!("SELECT COUNT(*) AS count FROM {}", identifier(table)));
+        if rows != *expected {
+            rows_check.problems += 1;
+        }
+        rows_check.details.push(format!("{}: {} rows, {} expected", table, rows, expected));
+    }
+    checks.push(rows_check);
+
+    ValidationReport{dataset: String::from(dataset), checks}
+}
+
+fn count(connector: &PgConnection, query: &str) -> usize {
+    let result = diesel::sql_query(query)
+        .get_result::<Count>(connector)
+        .unwrap_or_else(|error| panic!("Failed validation query {}: {}", query, error));
+    result.count as usize
+}
+
+fn identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let status = match (check.passed(), check.severity) {
+                (true, _) => "PASS",
+                (false, Severity::Warning) => "WARN",
+                (false, Severity::Error) => "FAIL"
+            };
+            writeln!(f, "[{}] {}: {}", status, check.name, check.problems)?;
+            for detail in &check.details {
+                writeln!(f, "       {}", detail)?;
+            }
+        }
+        writeln!(f, "{}: {}", self.dataset, if self.passed() { "PASS" } else { "FAIL" })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn warnings_dont_fail() {
+        let mut report = ValidationReport{
+            dataset: String::from("books"),
+            checks: vec![
+                Check::create("orphan ratings", Severity::Error, 0),
+                Check::create("users without ratings", Severity::Warning, 3),
+            ]
+        };
+        assert!(report.passed());
+
+        report.checks.push(Check::create("duplicated (user, item) ratings", Severity::Error, 1));
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn source_rows_leave_out_rejects() {
+        let dir = std::env::temp_dir().join("validate_source_rows");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ratings.csv"), "user,item,rating\n1,10,4\n1,10,5\n2,11,3\nx,11,2\n3,12,1\n").unwrap();
+        std::fs::write(dir.join("items.csv"), "10,Heat\n11,Ran\n").unwrap();
+        std::fs::write(dir.join("rejects.csv"), "table,file,line,reason,record\n\
+            users,ratings.csv,5,invalid user id,x;11;2\n\
+            ratings,ratings.csv,5,invalid rating user_id,x;11;2\n\
+            ratings,ratings.csv,6,rating of unknown item,3;12;1\n").unwrap();
+
+        let mapping: DatasetMapping = toml::from_str(r#"
+            database_url = "postgres://localhost/test"
+            reject_file = "rejects.csv"
+
+            [users]
+            table = "users"
+            file = "ratings.csv"
+            has_headers = true
+            allow_duplicates = true
+            id = { column = 0, name = "id", type = "integer" }
+
+            [items]
+            table = "movies"
+            file = "items.csv"
+            id = { column = 0, name = "id", type = "integer" }
+
+            [ratings]
+            table = "ratings"
+            file = "ratings.csv"
+            has_headers = true
+            user = { column = 0, name = "user_id", type = "integer" }
+            item = { column = 1, name = "movie_id", type = "integer" }
+            rating = { column = 2, name = "rating", type = "float" }
+        "#).unwrap();
+
+        let rows = source_rows(&mapping, &dir);
+        assert_eq!(rows["users"], 3);
+        assert_eq!(rows["movies"], 2);
+        assert_eq!(rows["ratings"], 2);
+    }
+}
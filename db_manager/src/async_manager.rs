@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream, StreamExt};
+
+use crate::{Connect, DBManager, User, Item, UserQuery, RatingKind, RatingRow, RatingScale, MatchMode, SearchResult, Signals, WriteError};
+
+pub type RatingsByUser<UID, IID> = HashMap<UID, HashMap<IID, f64>>;
+pub type RatingsByItem<IID, UID> = HashMap<IID, HashMap<UID, f64>>;
+pub type UserRatings<UID, IID> = (UID, HashMap<IID, f64>);
+
+/// Non-blocking counterpart of `DBManager`, for servers that can't wait on the database.
+/// Futures don't borrow the manager, so they can be spawned as tasks. `AsyncAdapter` implements
+/// it for the Postgres managers and `MemoryManager` for its in-memory dataset.
+pub trait AsyncDBManager<U: User<I>, I: Item>: Send + Sync {
+    fn rating_scale(&self) -> RatingScale;
+
+    fn get_user_by_name(&self, name: &str) -> BoxFuture<'static, Vec<U>>;
+    fn get_user_by_id(&self, uid: U::ID) -> BoxFuture<'static, Vec<U>>;
+    fn get_users_by_query(&self, query: &UserQuery) -> BoxFuture<'static, Vec<U>>;
+    fn get_item_by_name(&self, name: &str) -> BoxFuture<'static, Vec<I>>;
+    fn get_item_by_id(&self, uid: I::ID) -> BoxFuture<'static, Vec<I>>;
+    fn get_users_by_ids(&self, uids: &[U::ID]) -> BoxFuture<'static, HashMap<U::ID, U>>;
+    fn get_items_by_ids(&self, iids: &[I::ID]) -> BoxFuture<'static, HashMap<I::ID, I>>;
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> BoxFuture<'static, Vec<SearchResult<I>>>;
+    fn get_all_users(&self) -> BoxFuture<'static, Vec<U>>;
+    fn get_all_ratings(&self) -> BoxFuture<'static, RatingsByUser<U::ID, I::ID>>;
+    fn get_users_chunk(&self, after: Option<U::ID>, limit: i64) -> BoxFuture<'static, Vec<U::ID>>;
+    fn get_items_chunk(&self, after: Option<I::ID>, limit: i64) -> BoxFuture<'static, Vec<I>>;
+    fn get_user_ratings(&self, uid: U::ID) -> BoxFuture<'static, HashMap<I::ID, f64>>;
+    fn get_users_with_ratings_chunk(&self, after: Option<U::ID>, limit: i64) -> BoxFuture<'static, RatingsByUser<U::ID, I::ID>>;
+    fn get_item_ratings(&self, iid: I::ID) -> BoxFuture<'static, HashMap<U::ID, f64>>;
+    fn get_items_with_ratings_chunk(&self, after: Option<I::ID>, limit: i64) -> BoxFuture<'static, RatingsByItem<I::ID, U::ID>>;
+    fn get_item_rating_counts(&self) -> BoxFuture<'static, HashMap<I::ID, i64>>;
+    fn count_users(&self) -> BoxFuture<'static, i64>;
+    fn count_items(&self) -> BoxFuture<'static, i64>;
+    fn count_ratings(&self) -> BoxFuture<'static, i64>;
+    fn get_ratings_after(&self, after: i64, limit: i64) -> BoxFuture<'static, Vec<RatingRow<U::ID, I::ID>>>;
+    fn get_all_ratings_of(&self, kind: RatingKind) -> BoxFuture<'static, RatingsByUser<U::ID, I::ID>>;
+    fn get_user_ratings_of(&self, uid: U::ID, kind: RatingKind) -> BoxFuture<'static, HashMap<I::ID, f64>>;
+    fn get_all_signals(&self) -> BoxFuture<'static, Signals<U::ID, I::ID>>;
+
+    /// Explicit ratings of every user with ratings, read `chunk_size` users at a time.
+    fn ratings_stream(&self, chunk_size: i64) -> BoxStream<'static, UserRatings<U::ID, I::ID>>;
+
+    fn add_rating(&self, uid: U::ID, iid: I::ID, rating: f64) -> BoxFuture<'static, Result<(), WriteError>>;
+    fn delete_rating(&self, uid: U::ID, iid: I::ID) -> BoxFuture<'static, bool>;
+    fn create_user(&self, name: &str, data: &HashMap<String, String>) -> BoxFuture<'static, Result<U, WriteError>>;
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> BoxFuture<'static, Result<I, WriteError>>;
+}
+
+/// Runs the calls of a `DBManager` on Tokio's blocking threads, so any manager can be used as
+/// an `AsyncDBManager` with the same results. Futures have to be polled inside a Tokio runtime.
+pub struct AsyncAdapter<M> {
+    manager: Arc<M>
+}
+
+impl<M> Clone for AsyncAdapter<M> {
+    fn clone(&self) -> AsyncAdapter<M> {
+        AsyncAdapter{manager: self.manager.clone()}
+    }
+}
+
+impl<M: Send + Sync + 'static> AsyncAdapter<M> {
+    pub fn create(manager: M) -> AsyncAdapter<M> {
+        AsyncAdapter{manager: Arc::new(manager)}
+    }
+
+    /// Connects on a blocking thread too.
+    pub async fn connect_to(url: &str) -> AsyncAdapter<M>
+    where
+        M: Connect
+    {
+        let url = String::from(url);
+        let manager = run_blocking(move || M::connect_to(&url)).await;
+        AsyncAdapter{manager: Arc::new(manager)}
+    }
+
+    fn run<T, F>(&self, call: F) -> BoxFuture<'static, T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&M) -> T + Send + 'static
+    {
+        let manager = self.manager.clone();
+        Box::pin(run_blocking(move || call(&manager)))
+    }
+}
+
+/// Panics of the call are resumed in the task awaiting it, as the sync call would panic.
+async fn run_blocking<T, F>(call: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static
+{
+    match tokio::task::spawn_blocking(call).await {
+        Ok(result) => result,
+        Err(error) => std::panic::resume_unwind(error.into_panic())
+    }
+}
+
+impl<M, U, I> AsyncDBManager<U, I> for AsyncAdapter<M>
+where
+    M: DBManager<U, I> + Send + Sync + 'static,
+    U: User<I> + Send + 'static,
+    I: Item + Send + 'static,
+    U::ID: Clone + Send + 'static,
+    I::ID: Clone + Send + 'static
+{
+    fn rating_scale(&self) -> RatingScale {
+        self.manager.rating_scale()
+    }
+
+    fn get_user_by_name(&self, name: &str) -> BoxFuture<'static, Vec<U>> {
+        let name = String::from(name);
+        self.run(move |manager| manager.get_user_by_name(&name))
+    }
+    fn get_user_by_id(&self, uid: U::ID) -> BoxFuture<'static, Vec<U>> {
+        self.run(move |manager| manager.get_user_by_id(uid))
+    }
+    fn get_users_by_query(&self, query: &UserQuery) -> BoxFuture<'static, Vec<U>> {
+        let query = query.clone();
+        self.run(move |manager| manager.get_users_by_query(&query))
+    }
+    fn get_item_by_name(&self, name: &str) -> BoxFuture<'static, Vec<I>> {
+        let name = String::from(name);
+        self.run(move |manager| manager.get_item_by_name(&name))
+    }
+    fn get_item_by_id(&self, uid: I::ID) -> BoxFuture<'static, Vec<I>> {
+        self.run(move |manager| manager.get_item_by_id(uid))
+    }
+    fn get_users_by_ids(&self, uids: &[U::ID]) -> BoxFuture<'static, HashMap<U::ID, U>> {
+        let uids = uids.to_vec();
+        self.run(move |manager| manager.get_users_by_ids(&uids))
+    }
+    fn get_items_by_ids(&self, iids: &[I::ID]) -> BoxFuture<'static, HashMap<I::ID, I>> {
+        let iids = iids.to_vec();
+        self.run(move |manager| manager.get_items_by_ids(&iids))
+    }
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> BoxFuture<'static, Vec<SearchResult<I>>> {
+        let query = String::from(query);
+        self.run(move |manager| manager.search_items(&query, mode, limit))
+    }
+    fn get_all_users(&self) -> BoxFuture<'static, Vec<U>> {
+        self.run(|manager| manager.get_all_users())
+    }
+    fn get_all_ratings(&self) -> BoxFuture<'static, RatingsByUser<U::ID, I::ID>> {
+        self.run(|manager| manager.get_all_ratings())
+    }
+    fn get_users_chunk(&self, after: Option<U::ID>, limit: i64) -> BoxFuture<'static, Vec<U::ID>> {
+        self.run(move |manager| manager.get_users_chunk(after, limit))
+    }
+    fn get_items_chunk(&self, after: Option<I::ID>, limit: i64) -> BoxFuture<'static, Vec<I>> {
+        self.run(move |manager| manager.get_items_chunk(after, limit))
+    }
+    fn get_user_ratings(&self, uid: U::ID) -> BoxFuture<'static, HashMap<I::ID, f64>> {
+        self.run(move |manager| manager.get_user_ratings(uid))
+    }
+    fn get_users_with_ratings_chunk(&self, after: Option<U::ID>, limit: i64) -> BoxFuture<'static, RatingsByUser<U::ID, I::ID>> {
+        self.run(move |manager| manager.get_users_with_ratings_chunk(after, limit))
+    }
+    fn get_item_ratings(&self, iid: I::ID) -> BoxFuture<'static, HashMap<U::ID, f64>> {
+        self.run(move |manager| manager.get_item_ratings(iid))
+    }
+    fn get_items_with_ratings_chunk(&self, after: Option<I::ID>, limit: i64) -> BoxFuture<'static, RatingsByItem<I::ID, U::ID>> {
+        self.run(move |manager| manager.get_items_with_ratings_chunk(after, limit))
+    }
+    fn get_item_rating_counts(&self) -> BoxFuture<'static, HashMap<I::ID, i64>> {
+        self.run(|manager| manager.get_item_rating_counts())
+    }
+    fn count_users(&self) -> BoxFuture<'static, i64> {
+        self.run(|manager| manager.count_users())
+    }
+    fn count_items(&self) -> BoxFuture<'static, i64> {
+        self.run(|manager| manager.count_items())
+    }
+    fn count_ratings(&self) -> BoxFuture<'static, i64> {
+        self.run(|manager| manager.count_ratings())
+    }
+    fn get_ratings_after(&self, after: i64, limit: i64) -> BoxFuture<'static, Vec<RatingRow<U::ID, I::ID>>> {
+        self.run(move |manager| manager.get_ratings_after(after, limit))
+    }
+    fn get_all_ratings_of(&self, kind: RatingKind) -> BoxFuture<'static, RatingsByUser<U::ID, I::ID>> {
+        self.run(move |manager| manager.get_all_ratings_of(kind))
+    }
+    fn get_user_ratings_of(&self, uid: U::ID, kind: RatingKind) -> BoxFuture<'static, HashMap<I::ID, f64>> {
+        self.run(move |manager| manager.get_user_ratings_of(uid, kind))
+    }
+    fn get_all_signals(&self) -> BoxFuture<'static, Signals<U::ID, I::ID>> {
+        self.run(|manager| manager.get_all_signals())
+    }
+
+    fn ratings_stream(&self, chunk_size: i64) -> BoxStream<'static, UserRatings<U::ID, I::ID>> {
+        let adapter = self.clone();
+        // The state is the last user read, `None` once every user is read
+        let chunks = stream::unfold(Some(None), move |after: Option<Option<U::ID>>| {
+            let adapter = adapter.clone();
+            async move {
+                let after = after?;
+                let (ratings, last) = adapter.run(move |manager| {
+                    // The users chunk tells the end apart from a chunk of users without ratings
+                    let users = manager.get_users_chunk(after.clone(), chunk_size);
+                    users.last().map(|last| (manager.get_users_with_ratings_chunk(after, chunk_size), last.clone()))
+                }).await?;
+                Some((stream::iter(ratings), Some(Some(last))))
+            }
+        });
+        Box::pin(chunks.flatten())
+    }
+
+    fn add_rating(&self, uid: U::ID, iid: I::ID, rating: f64) -> BoxFuture<'static, Result<(), WriteError>> {
+        self.run(move |manager| manager.add_rating(uid, iid, rating))
+    }
+    fn delete_rating(&self, uid: U::ID, iid: I::ID) -> BoxFuture<'static, bool> {
+        self.run(move |manager| manager.delete_rating(uid, iid))
+    }
+    fn create_user(&self, name: &str, data: &HashMap<String, String>) -> BoxFuture<'static, Result<U, WriteError>> {
+        let (name, data) = (String::from(name), data.clone());
+        self.run(move |manager| manager.create_user(&name, &data))
+    }
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> BoxFuture<'static, Result<I, WriteError>> {
+        let (name, data) = (String::from(name), data.clone());
+        self.run(move |manager| manager.create_item(&name, &data))
+    }
+}
@@ -0,0 +1,485 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Connect, DBManager, User, Item, UserQuery, RatingKind, RatingRow, RatingScale, MatchMode, SearchResult, WriteError};
+
+/// Size and lifetime of the entries of one cached method.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CachePolicy {
+    /// Entries kept, the least recently used one is evicted first. 0 disables the cache.
+    pub capacity: usize,
+    /// Entries older than this are read again, `None` keeps them until they are evicted.
+    pub ttl: Option<Duration>
+}
+
+impl CachePolicy {
+    pub fn create(capacity: usize, ttl: Option<Duration>) -> CachePolicy {
+        CachePolicy{capacity, ttl}
+    }
+}
+
+/// Policies of the cached methods. The batch lookups share the cache of the lookup by id.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    pub user_by_id: CachePolicy,
+    pub user_by_name: CachePolicy,
+    pub item_by_id: CachePolicy,
+    pub item_by_name: CachePolicy,
+    pub user_ratings: CachePolicy,
+    pub item_ratings: CachePolicy
+}
+
+impl Default for CacheConfig {
+    fn default() -> CacheConfig {
+        let ttl = Some(Duration::from_secs(600));
+        CacheConfig{
+            user_by_id: CachePolicy::create(1000, ttl),
+            user_by_name: CachePolicy::create(100, ttl),
+            item_by_id: CachePolicy::create(1000, ttl),
+            item_by_name: CachePolicy::create(100, ttl),
+            user_ratings: CachePolicy::create(1000, ttl),
+            item_ratings: CachePolicy::create(1000, ttl)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    /// Lookups of missing or expired entries.
+    pub misses: u64,
+    pub evictions: u64
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f64 {
+        if self.hits + self.misses > 0 { self.hits as f64/(self.hits + self.misses) as f64 } else { 0.0 }
+    }
+}
+
+impl Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} hits, {} misses ({:.1}% hit rate), {} evictions", self.hits, self.misses, 100.0*self.hit_rate(), self.evictions)
+    }
+}
+
+struct Entry<V> {
+    value: V,
+    stored: Instant,
+    used: u64
+}
+
+/// Least recently used cache whose entries expire after the policy's TTL.
+struct Lru<K, V> {
+    policy: CachePolicy,
+    entries: HashMap<K, Entry<V>>,
+    /// Keys by last use, the first one is the least recently used.
+    order: BTreeMap<u64, K>,
+    tick: u64,
+    /// Bumped on every invalidation, values loaded while it changed may be stale and aren't kept.
+    generation: u64,
+    stats: CacheStats
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Lru<K, V> {
+    fn create(policy: CachePolicy) -> Lru<K, V> {
+        Lru{policy, entries: HashMap::new(), order: BTreeMap::new(), tick: 0, generation: 0, stats: CacheStats::default()}
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        let expired = match self.entries.get(key) {
+            Some(entry) => self.policy.ttl.is_some_and(|ttl| entry.stored.elapsed() >= ttl),
+            None => {
+                self.stats.misses += 1;
+                return None;
+            }
+        };
+        if expired {
+            self.remove(key);
+            self.stats.misses += 1;
+            return None;
+        }
+
+        self.tick += 1;
+        let entry = self.entries.get_mut(key).expect("The entry was just found");
+        self.order.remove(&entry.used);
+        entry.used = self.tick;
+        self.order.insert(self.tick, key.clone());
+        self.stats.hits += 1;
+        Some(entry.value.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.policy.capacity == 0 {
+            return;
+        }
+
+        self.remove(&key);
+        while self.entries.len() >= self.policy.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+                self.stats.evictions += 1;
+            }
+        }
+
+        self.tick += 1;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, Entry{value, stored: Instant::now(), used: self.tick});
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.used);
+        }
+    }
+
+    fn invalidate(&mut self, key: &K) {
+        self.remove(key);
+        self.generation += 1;
+    }
+
+    fn invalidate_where<F: Fn(&V) -> bool>(&mut self, stale: F) {
+        let keys: Vec<K> = self.entries.iter().filter(|(_, entry)| stale(&entry.value)).map(|(key, _)| key.clone()).collect();
+        for key in &keys {
+            self.remove(key);
+        }
+        self.generation += 1;
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+        self.generation += 1;
+    }
+}
+
+/// Looks `key` up in `cache`, calling `load` and keeping its result on a miss. The lock isn't
+/// held while loading, so concurrent misses of one key may both query the database, and the
+/// result isn't kept if the cache was invalidated in the meantime.
+fn cached<K, V, F>(cache: &Mutex<Lru<K, V>>, key: K, load: F) -> V
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    F: FnOnce() -> V
+{
+    let generation = {
+        let mut cache = lock(cache);
+        if let Some(value) = cache.get(&key) {
+            return value;
+        }
+        cache.generation
+    };
+
+    let value = load();
+    let mut cache = lock(cache);
+    if cache.generation == generation {
+        cache.insert(key, value.clone());
+    }
+    value
+}
+
+fn lock<K, V>(cache: &Mutex<Lru<K, V>>) -> std::sync::MutexGuard<'_, Lru<K, V>> {
+    cache.lock().expect("A thread panicked while using the cache")
+}
+
+type RatingsCache<K, ID> = Mutex<Lru<K, HashMap<ID, f64>>>;
+
+/// Wraps a manager keeping the users, items and ratings it reads, so repeated lookups don't
+/// query the database again. Writes made through the wrapper invalidate the entries they change,
+/// writes made elsewhere are seen once the entries expire or after `invalidate_user`/`invalidate_item`.
+pub struct CachingManager<M, U: User<I>, I: Item> {
+    manager: M,
+    user_by_id: Mutex<Lru<U::ID, Vec<U>>>,
+    user_by_name: Mutex<Lru<String, Vec<U>>>,
+    item_by_id: Mutex<Lru<I::ID, Vec<I>>>,
+    item_by_name: Mutex<Lru<String, Vec<I>>>,
+    user_ratings: RatingsCache<U::ID, I::ID>,
+    item_ratings: RatingsCache<I::ID, U::ID>
+}
+
+impl<M, U, I> CachingManager<M, U, I>
+where
+    U: User<I> + Clone,
+    I: Item + Clone,
+    U::ID: Hash + Eq + Clone,
+    I::ID: Hash + Eq + Clone
+{
+    pub fn create(manager: M, config: CacheConfig) -> CachingManager<M, U, I> {
+        CachingManager{
+            manager,
+            user_by_id: Mutex::new(Lru::create(config.user_by_id)),
+            user_by_name: Mutex::new(Lru::create(config.user_by_name)),
+            item_by_id: Mutex::new(Lru::create(config.item_by_id)),
+            item_by_name: Mutex::new(Lru::create(config.item_by_name)),
+            user_ratings: Mutex::new(Lru::create(config.user_ratings)),
+            item_ratings: Mutex::new(Lru::create(config.item_ratings))
+        }
+    }
+
+    /// Statistics of every cached method, by method name.
+    pub fn stats(&self) -> BTreeMap<&'static str, CacheStats> {
+        BTreeMap::from([
+            ("get_user_by_id", lock(&self.user_by_id).stats),
+            ("get_user_by_name", lock(&self.user_by_name).stats),
+            ("get_item_by_id", lock(&self.item_by_id).stats),
+            ("get_item_by_name", lock(&self.item_by_name).stats),
+            ("get_user_ratings", lock(&self.user_ratings).stats),
+            ("get_item_ratings", lock(&self.item_ratings).stats)
+        ])
+    }
+
+    /// Forgets the user and its ratings, for writes made around the wrapper.
+    pub fn invalidate_user(&self, uid: &U::ID) {
+        lock(&self.user_by_id).invalidate(uid);
+        lock(&self.user_by_name).invalidate_where(|users| users.iter().any(|user| user.id() == *uid));
+        lock(&self.user_ratings).invalidate(uid);
+    }
+
+    /// Forgets the item and the ratings it received, for writes made around the wrapper.
+    pub fn invalidate_item(&self, iid: &I::ID) {
+        lock(&self.item_by_id).invalidate(iid);
+        lock(&self.item_by_name).invalidate_where(|items| items.iter().any(|item| item.id() == *iid));
+        lock(&self.item_ratings).invalidate(iid);
+    }
+
+    pub fn clear(&self) {
+        lock(&self.user_by_id).clear();
+        lock(&self.user_by_name).clear();
+        lock(&self.item_by_id).clear();
+        lock(&self.item_by_name).clear();
+        lock(&self.user_ratings).clear();
+        lock(&self.item_ratings).clear();
+    }
+
+    pub fn manager(&self) -> &M {
+        &self.manager
+    }
+
+    /// A rating changes the user, with its ratings, and the ratings of the item.
+    fn invalidate_rating(&self, uid: &U::ID, iid: &I::ID) {
+        self.invalidate_user(uid);
+        lock(&self.item_ratings).invalidate(iid);
+    }
+}
+
+impl<M, U, I> Connect for CachingManager<M, U, I>
+where
+    M: Connect,
+    U: User<I> + Clone,
+    I: Item + Clone,
+    U::ID: Hash + Eq + Clone,
+    I::ID: Hash + Eq + Clone
+{
+    fn connect_to(url: &str) -> CachingManager<M, U, I> {
+        CachingManager::create(M::connect_to(url), CacheConfig::default())
+    }
+}
+
+impl<M, U, I> DBManager<U, I> for CachingManager<M, U, I>
+where
+    M: DBManager<U, I>,
+    U: User<I> + Clone,
+    I: Item + Clone,
+    U::ID: Hash + Eq + Clone,
+    I::ID: Hash + Eq + Clone
+{
+    fn rating_scale(&self) -> RatingScale {
+        self.manager.rating_scale()
+    }
+
+    fn get_user_by_name(&self, name: &str) -> Vec<U> {
+        cached(&self.user_by_name, String::from(name), || self.manager.get_user_by_name(name))
+    }
+    fn get_user_by_id(&self, uid: U::ID) -> Vec<U> {
+        cached(&self.user_by_id, uid.clone(), || self.manager.get_user_by_id(uid))
+    }
+    fn get_users_by_query(&self, query: &UserQuery) -> Vec<U> {
+        self.manager.get_users_by_query(query)
+    }
+    fn get_item_by_name(&self, name: &str) -> Vec<I> {
+        cached(&self.item_by_name, String::from(name), || self.manager.get_item_by_name(name))
+    }
+    fn get_item_by_id(&self, iid: I::ID) -> Vec<I> {
+        cached(&self.item_by_id, iid.clone(), || self.manager.get_item_by_id(iid))
+    }
+    /// Only the users missing from the cache are queried.
+    fn get_users_by_ids(&self, uids: &[U::ID]) -> HashMap<U::ID, U> {
+        let mut users = HashMap::new();
+        let mut missing = Vec::new();
+        let generation = {
+            let mut cache = lock(&self.user_by_id);
+            for uid in uids {
+                match cache.get(uid) {
+                    Some(found) => users.extend(found.into_iter().map(|user| (uid.clone(), user))),
+                    None => missing.push(uid.clone())
+                }
+            }
+            cache.generation
+        };
+        if missing.is_empty() {
+            return users;
+        }
+
+        let mut loaded = self.manager.get_users_by_ids(&missing);
+        let mut cache = lock(&self.user_by_id);
+        let current = cache.generation == generation;
+        for uid in missing {
+            // Ids not found are kept too, as `get_user_by_id` does
+            let found: Vec<U> = loaded.remove(&uid).into_iter().collect();
+            if current {
+                cache.insert(uid.clone(), found.clone());
+            }
+            users.extend(found.into_iter().map(|user| (uid.clone(), user)));
+        }
+        users
+    }
+    /// Only the items missing from the cache are queried.
+    fn get_items_by_ids(&self, iids: &[I::ID]) -> HashMap<I::ID, I> {
+        let mut items = HashMap::new();
+        let mut missing = Vec::new();
+        let generation = {
+            let mut cache = lock(&self.item_by_id);
+            for iid in iids {
+                match cache.get(iid) {
+                    Some(found) => items.extend(found.into_iter().map(|item| (iid.clone(), item))),
+                    None => missing.push(iid.clone())
+                }
+            }
+            cache.generation
+        };
+        if missing.is_empty() {
+            return items;
+        }
+
+        let mut loaded = self.manager.get_items_by_ids(&missing);
+        let mut cache = lock(&self.item_by_id);
+        let current = cache.generation == generation;
+        for iid in missing {
+            let found: Vec<I> = loaded.remove(&iid).into_iter().collect();
+            if current {
+                cache.insert(iid.clone(), found.clone());
+            }
+            items.extend(found.into_iter().map(|item| (iid.clone(), item)));
+        }
+        items
+    }
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> Vec<SearchResult<I>> {
+        self.manager.search_items(query, mode, limit)
+    }
+    fn get_all_users(&self) -> Vec<U> {
+        self.manager.get_all_users()
+    }
+    fn get_all_ratings(&self) -> HashMap<U::ID, HashMap<I::ID, f64>> {
+        self.manager.get_all_ratings()
+    }
+    fn get_users_chunk(&self, after: Option<U::ID>, limit: i64) -> Vec<U::ID> {
+        self.manager.get_users_chunk(after, limit)
+    }
+    fn get_items_chunk(&self, after: Option<I::ID>, limit: i64) -> Vec<I> {
+        self.manager.get_items_chunk(after, limit)
+    }
+    fn get_user_ratings(&self, uid: U::ID) -> HashMap<I::ID, f64> {
+        cached(&self.user_ratings, uid.clone(), || self.manager.get_user_ratings(uid))
+    }
+    fn get_users_with_ratings_chunk(&self, after: Option<U::ID>, limit: i64) -> HashMap<U::ID, HashMap<I::ID, f64>> {
+        self.manager.get_users_with_ratings_chunk(after, limit)
+    }
+    fn get_item_ratings(&self, iid: I::ID) -> HashMap<U::ID, f64> {
+        cached(&self.item_ratings, iid.clone(), || self.manager.get_item_ratings(iid))
+    }
+    fn get_items_with_ratings_chunk(&self, after: Option<I::ID>, limit: i64) -> HashMap<I::ID, HashMap<U::ID, f64>> {
+        self.manager.get_items_with_ratings_chunk(after, limit)
+    }
+    fn get_item_rating_counts(&self) -> HashMap<I::ID, i64> {
+        self.manager.get_item_rating_counts()
+    }
+    fn count_users(&self) -> i64 {
+        self.manager.count_users()
+    }
+    fn count_items(&self) -> i64 {
+        self.manager.count_items()
+    }
+    fn count_ratings(&self) -> i64 {
+        self.manager.count_ratings()
+    }
+    fn get_ratings_after(&self, after: i64, limit: i64) -> Vec<RatingRow<U::ID, I::ID>> {
+        self.manager.get_ratings_after(after, limit)
+    }
+    fn get_all_ratings_of(&self, kind: RatingKind) -> HashMap<U::ID, HashMap<I::ID, f64>> {
+        self.manager.get_all_ratings_of(kind)
+    }
+    fn get_user_ratings_of(&self, uid: U::ID, kind: RatingKind) -> HashMap<I::ID, f64> {
+        self.manager.get_user_ratings_of(uid, kind)
+    }
+
+    fn add_rating(&self, uid: U::ID, iid: I::ID, rating: f64) -> Result<(), WriteError> {
+        let result = self.manager.add_rating(uid.clone(), iid.clone(), rating);
+        if result.is_ok() {
+            self.invalidate_rating(&uid, &iid);
+        }
+        result
+    }
+    fn delete_rating(&self, uid: U::ID, iid: I::ID) -> bool {
+        let deleted = self.manager.delete_rating(uid.clone(), iid.clone());
+        if deleted {
+            self.invalidate_rating(&uid, &iid);
+        }
+        deleted
+    }
+    /// Lookups that found nothing before the user existed are forgotten.
+    fn create_user(&self, name: &str, data: &HashMap<String, String>) -> Result<U, WriteError> {
+        let user = self.manager.create_user(name, data)?;
+        lock(&self.user_by_id).invalidate(&user.id());
+        lock(&self.user_by_name).invalidate(&user.name());
+        lock(&self.user_by_name).invalidate(&String::from(name));
+        Ok(user)
+    }
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> Result<I, WriteError> {
+        let item = self.manager.create_item(name, data)?;
+        lock(&self.item_by_id).invalidate(&item.id());
+        lock(&self.item_by_name).invalidate(&item.name());
+        lock(&self.item_by_name).invalidate(&String::from(name));
+        Ok(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let mut cache = Lru::create(CachePolicy::create(2, None));
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        assert_eq!(cache.get(&1), Some("a"));
+
+        cache.insert(3, "c");
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some("a"));
+        assert_eq!(cache.get(&3), Some("c"));
+        assert_eq!(cache.stats, CacheStats{hits: 3, misses: 1, evictions: 1});
+
+        let mut expiring = Lru::create(CachePolicy::create(2, Some(Duration::ZERO)));
+        expiring.insert(1, "a");
+        assert_eq!(expiring.get(&1), None);
+        assert!(expiring.entries.is_empty() && expiring.order.is_empty());
+    }
+
+    #[test]
+    fn invalidated_loads_are_not_kept() {
+        let cache = Mutex::new(Lru::create(CachePolicy::create(2, None)));
+        let loaded = cached(&cache, 1, || {
+            lock(&cache).invalidate(&1);
+            "stale"
+        });
+        assert_eq!(loaded, "stale");
+        assert!(lock(&cache).entries.is_empty());
+
+        assert_eq!(cached(&cache, 1, || "fresh"), "fresh");
+        assert_eq!(cached(&cache, 1, || "reloaded"), "fresh");
+    }
+}
@@ -0,0 +1,499 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+
+use crate::{Connect, DBManager, User, Item, UserQuery, RatingKind, RatingRow, RatingScale, MatchMode, SearchResult, WriteError};
+
+/// ID of a user or item of any dataset, so managers can be used without knowing their ID types.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DynId {
+    Int(i32),
+    /// e.g. the ISBN of a book.
+    Text(String)
+}
+
+impl Display for DynId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DynId::Int(id) => write!(f, "{}", id),
+            DynId::Text(id) => write!(f, "{}", id)
+        }
+    }
+}
+
+/// ID types a manager can have to be used as a `DynDBManager`.
+pub trait DynIdType: Sized {
+    fn into_dyn(self) -> DynId;
+    /// `None` if the ID is of another kind.
+    fn from_dyn(id: DynId) -> Option<Self>;
+    fn parse_dyn(raw: &str) -> Option<DynId>;
+}
+
+impl DynIdType for i32 {
+    fn into_dyn(self) -> DynId {
+        DynId::Int(self)
+    }
+    fn from_dyn(id: DynId) -> Option<i32> {
+        match id {
+            DynId::Int(id) => Some(id),
+            DynId::Text(_) => None
+        }
+    }
+    fn parse_dyn(raw: &str) -> Option<DynId> {
+        raw.trim().parse().ok().map(DynId::Int)
+    }
+}
+
+impl DynIdType for String {
+    fn into_dyn(self) -> DynId {
+        DynId::Text(self)
+    }
+    fn from_dyn(id: DynId) -> Option<String> {
+        match id {
+            DynId::Text(id) => Some(id),
+            DynId::Int(_) => None
+        }
+    }
+    fn parse_dyn(raw: &str) -> Option<DynId> {
+        Some(DynId::Text(String::from(raw)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DynUser {
+    pub id: DynId,
+    pub name: String,
+    pub data: HashMap<String, String>,
+    pub ratings: HashMap<DynId, f64>
+}
+
+impl User<DynItem> for DynUser {
+    type ID = DynId;
+
+    fn id(&self) -> DynId {
+        self.id.clone()
+    }
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn data(&self) -> HashMap<String, String> {
+        self.data.clone()
+    }
+    fn ratings(&self) -> HashMap<DynId, f64> {
+        self.ratings.clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DynItem {
+    pub id: DynId,
+    pub name: String,
+    pub data: HashMap<String, String>
+}
+
+impl Item for DynItem {
+    type ID = DynId;
+
+    fn id(&self) -> DynId {
+        self.id.clone()
+    }
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+    fn data(&self) -> HashMap<String, String> {
+        self.data.clone()
+    }
+}
+
+/// Object-safe counterpart of `DBManager` with `DynId`s. IDs of the wrong kind for the dataset
+/// match nothing, as an unknown ID would.
+pub trait DynDBManager: Send + Sync {
+    fn rating_scale(&self) -> RatingScale;
+
+    /// Parses an ID typed by a person, e.g. a command line argument.
+    fn parse_user_id(&self, raw: &str) -> Option<DynId>;
+    fn parse_item_id(&self, raw: &str) -> Option<DynId>;
+
+    fn get_user_by_name(&self, name: &str) -> Vec<DynUser>;
+    fn get_user_by_id(&self, uid: DynId) -> Vec<DynUser>;
+    fn get_users_by_query(&self, query: &UserQuery) -> Vec<DynUser>;
+    fn get_item_by_name(&self, name: &str) -> Vec<DynItem>;
+    fn get_item_by_id(&self, uid: DynId) -> Vec<DynItem>;
+    fn get_users_by_ids(&self, uids: &[DynId]) -> HashMap<DynId, DynUser>;
+    fn get_items_by_ids(&self, iids: &[DynId]) -> HashMap<DynId, DynItem>;
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> Vec<SearchResult<DynItem>>;
+    fn get_all_users(&self) -> Vec<DynUser>;
+    fn get_all_ratings(&self) -> HashMap<DynId, HashMap<DynId, f64>>;
+    fn get_users_chunk(&self, after: Option<DynId>, limit: i64) -> Vec<DynId>;
+    fn get_items_chunk(&self, after: Option<DynId>, limit: i64) -> Vec<DynItem>;
+    fn get_user_ratings(&self, uid: DynId) -> HashMap<DynId, f64>;
+    fn get_users_with_ratings_chunk(&self, after: Option<DynId>, limit: i64) -> HashMap<DynId, HashMap<DynId, f64>>;
+    fn get_item_ratings(&self, iid: DynId) -> HashMap<DynId, f64>;
+    fn get_items_with_ratings_chunk(&self, after: Option<DynId>, limit: i64) -> HashMap<DynId, HashMap<DynId, f64>>;
+    fn get_item_rating_counts(&self) -> HashMap<DynId, i64>;
+    fn count_users(&self) -> i64;
+    fn count_items(&self) -> i64;
+    fn count_ratings(&self) -> i64;
+    fn get_ratings_after(&self, after: i64, limit: i64) -> Vec<RatingRow<DynId, DynId>>;
+    fn get_all_ratings_of(&self, kind: RatingKind) -> HashMap<DynId, HashMap<DynId, f64>>;
+    fn get_user_ratings_of(&self, uid: DynId, kind: RatingKind) -> HashMap<DynId, f64>;
+
+    fn add_rating(&self, uid: DynId, iid: DynId, rating: f64) -> Result<(), WriteError>;
+    fn delete_rating(&self, uid: DynId, iid: DynId) -> bool;
+    fn create_user(&self, name: &str, data: &HashMap<String, String>) -> Result<DynUser, WriteError>;
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> Result<DynItem, WriteError>;
+}
+
+/// Wraps a `DBManager` to be used as a `DynDBManager`.
+pub struct DynAdapter<M, U, I> {
+    pub manager: M,
+    phantom: PhantomData<fn() -> (U, I)>
+}
+
+impl<M, U, I> DynAdapter<M, U, I> {
+    pub fn create(manager: M) -> DynAdapter<M, U, I> {
+        DynAdapter{manager, phantom: PhantomData}
+    }
+}
+
+/// Connects to `url` with `M` and boxes it, can be given to `Registry::register`.
+pub fn connect_boxed<M, U, I>(url: &str) -> Box<dyn DynDBManager>
+where
+    M: DBManager<U, I> + Connect + Send + Sync + 'static,
+    U: User<I> + 'static,
+    I: Item + 'static,
+    U::ID: DynIdType,
+    I::ID: DynIdType
+{
+    Box::new(DynAdapter::create(M::connect_to(url)))
+}
+
+fn dyn_user<U: User<I>, I: Item>(user: U) -> DynUser
+where
+    U::ID: DynIdType,
+    I::ID: DynIdType
+{
+    DynUser{id: user.id().into_dyn(), name: user.name(), data: user.data(), ratings: dyn_ratings(user.ratings())}
+}
+
+fn dyn_item<I: Item>(item: I) -> DynItem
+where
+    I::ID: DynIdType
+{
+    DynItem{id: item.id().into_dyn(), name: item.name(), data: item.data()}
+}
+
+fn dyn_ratings<ID: DynIdType>(ratings: HashMap<ID, f64>) -> HashMap<DynId, f64> {
+    ratings.into_iter().map(|(id, rating)| (id.into_dyn(), rating)).collect()
+}
+
+/// Ratings by user, or by item.
+fn dyn_ratings_by_user<UID: DynIdType, IID: DynIdType>(ratings: HashMap<UID, HashMap<IID, f64>>) -> HashMap<DynId, HashMap<DynId, f64>> {
+    ratings.into_iter().map(|(id, user_ratings)| (id.into_dyn(), dyn_ratings(user_ratings))).collect()
+}
+
+impl<M, U, I> DynDBManager for DynAdapter<M, U, I>
+where
+    M: DBManager<U, I> + Send + Sync,
+    U: User<I>,
+    I: Item,
+    U::ID: DynIdType,
+    I::ID: DynIdType
+{
+    fn rating_scale(&self) -> RatingScale {
+        self.manager.rating_scale()
+    }
+
+    fn parse_user_id(&self, raw: &str) -> Option<DynId> {
+        U::ID::parse_dyn(raw)
+    }
+    fn parse_item_id(&self, raw: &str) -> Option<DynId> {
+        I::ID::parse_dyn(raw)
+    }
+
+    fn get_user_by_name(&self, name: &str) -> Vec<DynUser> {
+        self.manager.get_user_by_name(name).into_iter().map(dyn_user).collect()
+    }
+    fn get_user_by_id(&self, uid: DynId) -> Vec<DynUser> {
+        match U::ID::from_dyn(uid) {
+            Some(uid) => self.manager.get_user_by_id(uid).into_iter().map(dyn_user).collect(),
+            None => Vec::new()
+        }
+    }
+    fn get_users_by_query(&self, query: &UserQuery) -> Vec<DynUser> {
+        self.manager.get_users_by_query(query).into_iter().map(dyn_user).collect()
+    }
+    fn get_item_by_name(&self, name: &str) -> Vec<DynItem> {
+        self.manager.get_item_by_name(name).into_iter().map(dyn_item).collect()
+    }
+    fn get_item_by_id(&self, uid: DynId) -> Vec<DynItem> {
+        match I::ID::from_dyn(uid) {
+            Some(uid) => self.manager.get_item_by_id(uid).into_iter().map(dyn_item).collect(),
+            None => Vec::new()
+        }
+    }
+    fn get_users_by_ids(&self, uids: &[DynId]) -> HashMap<DynId, DynUser> {
+        let uids: Vec<U::ID> = uids.iter().cloned().filter_map(U::ID::from_dyn).collect();
+        self.manager.get_users_by_ids(&uids).into_iter()
+            .map(|(id, user)| (id.into_dyn(), dyn_user(user)))
+            .collect()
+    }
+    fn get_items_by_ids(&self, iids: &[DynId]) -> HashMap<DynId, DynItem> {
+        let iids: Vec<I::ID> = iids.iter().cloned().filter_map(I::ID::from_dyn).collect();
+        self.manager.get_items_by_ids(&iids).into_iter()
+            .map(|(id, item)| (id.into_dyn(), dyn_item(item)))
+            .collect()
+    }
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> Vec<SearchResult<DynItem>> {
+        self.manager.search_items(query, mode, limit).into_iter()
+            .map(|result| SearchResult{item: dyn_item(result.item), score: result.score})
+            .collect()
+    }
+    fn get_all_users(&self) -> Vec<DynUser> {
+        self.manager.get_all_users().into_iter().map(dyn_user).collect()
+    }
+    fn get_all_ratings(&self) -> HashMap<DynId, HashMap<DynId, f64>> {
+        dyn_ratings_by_user(self.manager.get_all_ratings())
+    }
+    fn get_users_chunk(&self, after: Option<DynId>, limit: i64) -> Vec<DynId> {
+        match after.map(U::ID::from_dyn) {
+            Some(None) => Vec::new(),
+            after => self.manager.get_users_chunk(after.flatten(), limit).into_iter().map(DynIdType::into_dyn).collect()
+        }
+    }
+    fn get_items_chunk(&self, after: Option<DynId>, limit: i64) -> Vec<DynItem> {
+        match after.map(I::ID::from_dyn) {
+            Some(None) => Vec::new(),
+            after => self.manager.get_items_chunk(after.flatten(), limit).into_iter().map(dyn_item).collect()
+        }
+    }
+    fn get_user_ratings(&self, uid: DynId) -> HashMap<DynId, f64> {
+        match U::ID::from_dyn(uid) {
+            Some(uid) => dyn_ratings(self.manager.get_user_ratings(uid)),
+            None => HashMap::new()
+        }
+    }
+    fn get_users_with_ratings_chunk(&self, after: Option<DynId>, limit: i64) -> HashMap<DynId, HashMap<DynId, f64>> {
+        match after.map(U::ID::from_dyn) {
+            Some(None) => HashMap::new(),
+            after => dyn_ratings_by_user(self.manager.get_users_with_ratings_chunk(after.flatten(), limit))
+        }
+    }
+    fn get_item_ratings(&self, iid: DynId) -> HashMap<DynId, f64> {
+        match I::ID::from_dyn(iid) {
+            Some(iid) => dyn_ratings(self.manager.get_item_ratings(iid)),
+            None => HashMap::new()
+        }
+    }
+    fn get_items_with_ratings_chunk(&self, after: Option<DynId>, limit: i64) -> HashMap<DynId, HashMap<DynId, f64>> {
+        match after.map(I::ID::from_dyn) {
+            Some(None) => HashMap::new(),
+            after => dyn_ratings_by_user(self.manager.get_items_with_ratings_chunk(after.flatten(), limit))
+        }
+    }
+    fn get_item_rating_counts(&self) -> HashMap<DynId, i64> {
+        self.manager.get_item_rating_counts().into_iter().map(|(id, count)| (id.into_dyn(), count)).collect()
+    }
+    fn count_users(&self) -> i64 {
+        self.manager.count_users()
+    }
+    fn count_items(&self) -> i64 {
+        self.manager.count_items()
+    }
+    fn count_ratings(&self) -> i64 {
+        self.manager.count_ratings()
+    }
+    fn get_ratings_after(&self, after: i64, limit: i64) -> Vec<RatingRow<DynId, DynId>> {
+        self.manager.get_ratings_after(after, limit).into_iter()
+            .map(|row| RatingRow{key: row.key, user: row.user.into_dyn(), item: row.item.into_dyn(), rating: row.rating})
+            .collect()
+    }
+    fn get_all_ratings_of(&self, kind: RatingKind) -> HashMap<DynId, HashMap<DynId, f64>> {
+        dyn_ratings_by_user(self.manager.get_all_ratings_of(kind))
+    }
+    fn get_user_ratings_of(&self, uid: DynId, kind: RatingKind) -> HashMap<DynId, f64> {
+        match U::ID::from_dyn(uid) {
+            Some(uid) => dyn_ratings(self.manager.get_user_ratings_of(uid, kind)),
+            None => HashMap::new()
+        }
+    }
+
+    fn add_rating(&self, uid: DynId, iid: DynId, rating: f64) -> Result<(), WriteError> {
+        let uid = U::ID::from_dyn(uid).ok_or(WriteError::UnknownUser)?;
+        let iid = I::ID::from_dyn(iid).ok_or(WriteError::UnknownItem)?;
+        self.manager.add_rating(uid, iid, rating)
+    }
+    fn delete_rating(&self, uid: DynId, iid: DynId) -> bool {
+        match (U::ID::from_dyn(uid), I::ID::from_dyn(iid)) {
+            (Some(uid), Some(iid)) => self.manager.delete_rating(uid, iid),
+            _ => false
+        }
+    }
+    fn create_user(&self, name: &str, data: &HashMap<String, String>) -> Result<DynUser, WriteError> {
+        self.manager.create_user(name, data).map(dyn_user)
+    }
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> Result<DynItem, WriteError> {
+        self.manager.create_item(name, data).map(dyn_item)
+    }
+}
+
+/// Lets the generic code take managers chosen at runtime. Boxed managers are connected through
+/// a `Registry`, by the name of their dataset, so they don't implement `Connect`.
+impl DBManager<DynUser, DynItem> for Box<dyn DynDBManager> {
+    fn rating_scale(&self) -> RatingScale {
+        (**self).rating_scale()
+    }
+
+    fn get_user_by_name(&self, name: &str) -> Vec<DynUser> {
+        (**self).get_user_by_name(name)
+    }
+    fn get_user_by_id(&self, uid: DynId) -> Vec<DynUser> {
+        (**self).get_user_by_id(uid)
+    }
+    fn get_users_by_query(&self, query: &UserQuery) -> Vec<DynUser> {
+        (**self).get_users_by_query(query)
+    }
+    fn get_item_by_name(&self, name: &str) -> Vec<DynItem> {
+        (**self).get_item_by_name(name)
+    }
+    fn get_item_by_id(&self, uid: DynId) -> Vec<DynItem> {
+        (**self).get_item_by_id(uid)
+    }
+    fn get_users_by_ids(&self, uids: &[DynId]) -> HashMap<DynId, DynUser> {
+        (**self).get_users_by_ids(uids)
+    }
+    fn get_items_by_ids(&self, iids: &[DynId]) -> HashMap<DynId, DynItem> {
+        (**self).get_items_by_ids(iids)
+    }
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> Vec<SearchResult<DynItem>> {
+        (**self).search_items(query, mode, limit)
+    }
+    fn get_all_users(&self) -> Vec<DynUser> {
+        (**self).get_all_users()
+    }
+    fn get_all_ratings(&self) -> HashMap<DynId, HashMap<DynId, f64>> {
+        (**self).get_all_ratings()
+    }
+    fn get_users_chunk(&self, after: Option<DynId>, limit: i64) -> Vec<DynId> {
+        (**self).get_users_chunk(after, limit)
+    }
+    fn get_items_chunk(&self, after: Option<DynId>, limit: i64) -> Vec<DynItem> {
+        (**self).get_items_chunk(after, limit)
+    }
+    fn get_user_ratings(&self, uid: DynId) -> HashMap<DynId, f64> {
+        (**self).get_user_ratings(uid)
+    }
+    fn get_users_with_ratings_chunk(&self, after: Option<DynId>, limit: i64) -> HashMap<DynId, HashMap<DynId, f64>> {
+        (**self).get_users_with_ratings_chunk(after, limit)
+    }
+    fn get_item_ratings(&self, iid: DynId) -> HashMap<DynId, f64> {
+        (**self).get_item_ratings(iid)
+    }
+    fn get_items_with_ratings_chunk(&self, after: Option<DynId>, limit: i64) -> HashMap<DynId, HashMap<DynId, f64>> {
+        (**self).get_items_with_ratings_chunk(after, limit)
+    }
+    fn get_item_rating_counts(&self) -> HashMap<DynId, i64> {
+        (**self).get_item_rating_counts()
+    }
+    fn count_users(&self) -> i64 {
+        (**self).count_users()
+    }
+    fn count_items(&self) -> i64 {
+        (**self).count_items()
+    }
+    fn count_ratings(&self) -> i64 {
+        (**self).count_ratings()
+    }
+    fn get_ratings_after(&self, after: i64, limit: i64) -> Vec<RatingRow<DynId, DynId>> {
+        (**self).get_ratings_after(after, limit)
+    }
+    fn get_all_ratings_of(&self, kind: RatingKind) -> HashMap<DynId, HashMap<DynId, f64>> {
+        (**self).get_all_ratings_of(kind)
+    }
+    fn get_user_ratings_of(&self, uid: DynId, kind: RatingKind) -> HashMap<DynId, f64> {
+        (**self).get_user_ratings_of(uid, kind)
+    }
+
+    fn add_rating(&self, uid: DynId, iid: DynId, rating: f64) -> Result<(), WriteError> {
+        (**self).add_rating(uid, iid, rating)
+    }
+    fn delete_rating(&self, uid: DynId, iid: DynId) -> bool {
+        (**self).delete_rating(uid, iid)
+    }
+    fn create_user(&self, name: &str, data: &HashMap<String, String>) -> Result<DynUser, WriteError> {
+        (**self).create_user(name, data)
+    }
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> Result<DynItem, WriteError> {
+        (**self).create_item(name, data)
+    }
+}
+
+struct Backend {
+    default_url: String,
+    connect: fn(&str) -> Box<dyn DynDBManager>
+}
+
+/// Dataset backends by name, so the dataset can be chosen at runtime.
+#[derive(Default)]
+pub struct Registry {
+    backends: BTreeMap<String, Backend>
+}
+
+impl Registry {
+    pub fn create() -> Registry {
+        Registry::default()
+    }
+
+    /// Registers the dataset `name`, stored with the schema of `M` at `default_url`.
+    pub fn register<M, U, I>(&mut self, name: &str, default_url: &str)
+    where
+        M: DBManager<U, I> + Connect + Send + Sync + 'static,
+        U: User<I> + 'static,
+        I: Item + 'static,
+        U::ID: DynIdType,
+        I::ID: DynIdType
+    {
+        self.backends.insert(String::from(name), Backend{default_url: String::from(default_url), connect: connect_boxed::<M, U, I>});
+    }
+
+    /// Names of the registered datasets, in alphabetical order.
+    pub fn datasets(&self) -> Vec<String> {
+        self.backends.keys().cloned().collect()
+    }
+
+    /// Connects to the dataset at its default URL, `None` if it isn't registered.
+    pub fn connect(&self, name: &str) -> Option<Box<dyn DynDBManager>> {
+        let backend = self.backends.get(name)?;
+        Some((backend.connect)(&backend.default_url))
+    }
+
+    /// Default URL of the dataset, `None` if it isn't registered.
+    pub fn url(&self, name: &str) -> Option<&str> {
+        self.backends.get(name).map(|backend| backend.default_url.as_str())
+    }
+
+    pub fn connect_to(&self, name: &str, url: &str) -> Option<Box<dyn DynDBManager>> {
+        let backend = self.backends.get(name)?;
+        Some((backend.connect)(url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dynamic_ids() {
+        assert_eq!(i32::parse_dyn(" 18"), Some(DynId::Int(18)));
+        assert_eq!(i32::parse_dyn("0195153448X"), None);
+        assert_eq!(String::parse_dyn("0195153448"), Some(DynId::Text(String::from("0195153448"))));
+
+        assert_eq!(i32::from_dyn(18.into_dyn()), Some(18));
+        assert_eq!(i32::from_dyn(DynId::Text(String::from("18"))), None);
+        assert_eq!(DynId::Text(String::from("0195153448")).to_string(), "0195153448");
+
+        let registry = Registry::create();
+        assert!(registry.datasets().is_empty());
+        assert!(registry.connect("books").is_none());
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+
+/// Bind parameters Postgres accepts in a statement, a multi-row `INSERT` takes as many rows as fit.
+pub const MAX_BIND_PARAMS: usize = 65535;
+
+/// Rows per multi-row `INSERT` for a table with `columns` columns.
+pub fn insert_chunk_size(columns: usize) -> usize {
+    MAX_BIND_PARAMS/columns
+}
+
+/// Summary of a dataset load: rows inserted per table and rows dropped per reason.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub inserted: BTreeMap<String, usize>,
+    pub dropped: BTreeMap<String, usize>
+}
+
+impl ImportReport {
+    pub fn record_inserted(&mut self, table: &str, rows: usize) {
+        *self.inserted.entry(String::from(table)).or_insert(0) += rows;
+    }
+
+    pub fn record_dropped(&mut self, reason: &str) {
+        *self.dropped.entry(String::from(reason)).or_insert(0) += 1;
+    }
+
+    pub fn total_dropped(&self) -> usize {
+        self.dropped.values().sum()
+    }
+}
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Inserted rows:")?;
+        for (table, rows) in &self.inserted {
+            writeln!(f, "  {}: {}", table, rows)?;
+        }
+        writeln!(f, "Dropped rows: {}", self.total_dropped())?;
+        for (reason, rows) in &self.dropped {
+            writeln!(f, "  {}: {}", reason, rows)?;
+        }
+        Ok(())
+    }
+}
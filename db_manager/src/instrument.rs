@@ -0,0 +1,317 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Connect, DBManager, User, Item, UserQuery, RatingKind, RatingRow, RatingScale, MatchMode, SearchResult, WriteError};
+
+/// Calls of one method and what they took.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MethodStats {
+    pub calls: u64,
+    pub total: Duration,
+    pub max: Duration,
+    /// Users, items or ratings returned, ratings are counted one by one in maps of ratings.
+    pub rows: u64
+}
+
+impl MethodStats {
+    pub fn mean(&self) -> Duration {
+        if self.calls > 0 { self.total.div_f64(self.calls as f64) } else { Duration::ZERO }
+    }
+}
+
+/// Statistics of every method called, printed as a table with the slowest methods first.
+#[derive(Debug, Clone, Default)]
+pub struct QueryReport {
+    pub methods: BTreeMap<&'static str, MethodStats>
+}
+
+impl QueryReport {
+    pub fn total(&self) -> Duration {
+        self.methods.values().map(|stats| stats.total).sum()
+    }
+}
+
+impl Display for QueryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut methods: Vec<(&&str, &MethodStats)> = self.methods.iter().collect();
+        methods.sort_by_key(|(_, stats)| Reverse(stats.total));
+
+        writeln!(f, "{:<30} {:>8} {:>12} {:>12} {:>12} {:>10}", "Method", "Calls", "Total (ms)", "Mean (ms)", "Max (ms)", "Rows")?;
+        for (method, stats) in methods {
+            writeln!(
+                f, "{:<30} {:>8} {:>12.3} {:>12.3} {:>12.3} {:>10}",
+                method, stats.calls, millis(stats.total), millis(stats.mean()), millis(stats.max), stats.rows
+            )?;
+        }
+        writeln!(f, "Total database time: {:.3} ms", millis(self.total()))
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64()*1000.0
+}
+
+/// Wraps a manager recording the calls, latency and rows returned of every method, to find the
+/// queries that dominate a run. Only the trait methods called on the wrapper are seen, not the
+/// queries they make: e.g. `get_all_users`, which queries the ratings of every user on its own,
+/// counts as one call with the time of all its queries.
+pub struct InstrumentedManager<M> {
+    manager: M,
+    methods: Mutex<BTreeMap<&'static str, MethodStats>>
+}
+
+impl<M> InstrumentedManager<M> {
+    pub fn create(manager: M) -> InstrumentedManager<M> {
+        InstrumentedManager{manager, methods: Mutex::new(BTreeMap::new())}
+    }
+
+    pub fn report(&self) -> QueryReport {
+        QueryReport{methods: self.lock().clone()}
+    }
+
+    pub fn reset(&self) {
+        self.lock().clear();
+    }
+
+    pub fn manager(&self) -> &M {
+        &self.manager
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<&'static str, MethodStats>> {
+        self.methods.lock().expect("A thread panicked while recording a call")
+    }
+
+    /// Times `call` and records it under `method` with the rows `rows` counts in its result.
+    fn record<T, F, R>(&self, method: &'static str, call: F, rows: R) -> T
+    where
+        F: FnOnce(&M) -> T,
+        R: FnOnce(&T) -> usize
+    {
+        let start = Instant::now();
+        let result = call(&self.manager);
+        let elapsed = start.elapsed();
+        let rows = rows(&result) as u64;
+
+        let mut methods = self.lock();
+        let stats = methods.entry(method).or_default();
+        stats.calls += 1;
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+        stats.rows += rows;
+        result
+    }
+}
+
+fn nested_len<K, V>(map: &HashMap<K, HashMap<V, f64>>) -> usize {
+    map.values().map(|ratings| ratings.len()).sum()
+}
+
+impl<M: Connect> Connect for InstrumentedManager<M> {
+    fn connect_to(url: &str) -> InstrumentedManager<M> {
+        InstrumentedManager::create(M::connect_to(url))
+    }
+}
+
+impl<M, U, I> DBManager<U, I> for InstrumentedManager<M>
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item
+{
+    fn rating_scale(&self) -> RatingScale {
+        self.manager.rating_scale()
+    }
+
+    fn get_user_by_name(&self, name: &str) -> Vec<U> {
+        self.record("get_user_by_name", |manager| manager.get_user_by_name(name), Vec::len)
+    }
+    fn get_user_by_id(&self, uid: U::ID) -> Vec<U> {
+        self.record("get_user_by_id", |manager| manager.get_user_by_id(uid), Vec::len)
+    }
+    fn get_users_by_query(&self, query: &UserQuery) -> Vec<U> {
+        self.record("get_users_by_query", |manager| manager.get_users_by_query(query), Vec::len)
+    }
+    fn get_item_by_name(&self, name: &str) -> Vec<I> {
+        self.record("get_item_by_name", |manager| manager.get_item_by_name(name), Vec::len)
+    }
+    fn get_item_by_id(&self, iid: I::ID) -> Vec<I> {
+        self.record("get_item_by_id", |manager| manager.get_item_by_id(iid), Vec::len)
+    }
+    fn get_users_by_ids(&self, uids: &[U::ID]) -> HashMap<U::ID, U> {
+        self.record("get_users_by_ids", |manager| manager.get_users_by_ids(uids), HashMap::len)
+    }
+    fn get_items_by_ids(&self, iids: &[I::ID]) -> HashMap<I::ID, I> {
+        self.record("get_items_by_ids", |manager| manager.get_items_by_ids(iids), HashMap::len)
+    }
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> Vec<SearchResult<I>> {
+        self.record("search_items", |manager| manager.search_items(query, mode, limit), Vec::len)
+    }
+    fn get_all_users(&self) -> Vec<U> {
+        self.record("get_all_users", |manager| manager.get_all_users(), Vec::len)
+    }
+    fn get_all_ratings(&self) -> HashMap<U::ID, HashMap<I::ID, f64>> {
+        self.record("get_all_ratings", |manager| manager.get_all_ratings(), nested_len)
+    }
+    fn get_users_chunk(&self, after: Option<U::ID>, limit: i64) -> Vec<U::ID> {
+        self.record("get_users_chunk", |manager| manager.get_users_chunk(after, limit), Vec::len)
+    }
+    fn get_items_chunk(&self, after: Option<I::ID>, limit: i64) -> Vec<I> {
+        self.record("get_items_chunk", |manager| manager.get_items_chunk(after, limit), Vec::len)
+    }
+    fn get_user_ratings(&self, uid: U::ID) -> HashMap<I::ID, f64> {
+        self.record("get_user_ratings", |manager| manager.get_user_ratings(uid), HashMap::len)
+    }
+    fn get_users_with_ratings_chunk(&self, after: Option<U::ID>, limit: i64) -> HashMap<U::ID, HashMap<I::ID, f64>> {
+        self.record("get_users_with_ratings_chunk", |manager| manager.get_users_with_ratings_chunk(after, limit), nested_len)
+    }
+    fn get_item_ratings(&self, iid: I::ID) -> HashMap<U::ID, f64> {
+        self.record("get_item_ratings", |manager| manager.get_item_ratings(iid), HashMap::len)
+    }
+    fn get_items_with_ratings_chunk(&self, after: Option<I::ID>, limit: i64) -> HashMap<I::ID, HashMap<U::ID, f64>> {
+        self.record("get_items_with_ratings_chunk", |manager| manager.get_items_with_ratings_chunk(after, limit), nested_len)
+    }
+    fn get_item_rating_counts(&self) -> HashMap<I::ID, i64> {
+        self.record("get_item_rating_counts", |manager| manager.get_item_rating_counts(), HashMap::len)
+    }
+    fn count_users(&self) -> i64 {
+        self.record("count_users", |manager| manager.count_users(), |_| 1)
+    }
+    fn count_items(&self) -> i64 {
+        self.record("count_items", |manager| manager.count_items(), |_| 1)
+    }
+    fn count_ratings(&self) -> i64 {
+        self.record("count_ratings", |manager| manager.count_ratings(), |_| 1)
+    }
+    fn get_ratings_after(&self, after: i64, limit: i64) -> Vec<RatingRow<U::ID, I::ID>> {
+        self.record("get_ratings_after", |manager| manager.get_ratings_after(after, limit), Vec::len)
+    }
+    fn get_all_ratings_of(&self, kind: RatingKind) -> HashMap<U::ID, HashMap<I::ID, f64>> {
+        self.record("get_all_ratings_of", |manager| manager.get_all_ratings_of(kind), nested_len)
+    }
+    fn get_user_ratings_of(&self, uid: U::ID, kind: RatingKind) -> HashMap<I::ID, f64> {
+        self.record("get_user_ratings_of", |manager| manager.get_user_ratings_of(uid, kind), HashMap::len)
+    }
+
+    fn add_rating(&self, uid: U::ID, iid: I::ID, rating: f64) -> Result<(), WriteError> {
+        self.record("add_rating", |manager| manager.add_rating(uid, iid, rating), |result| result.is_ok() as usize)
+    }
+    fn delete_rating(&self, uid: U::ID, iid: I::ID) -> bool {
+        self.record("delete_rating", |manager| manager.delete_rating(uid, iid), |deleted| *deleted as usize)
+    }
+    fn create_user(&self, name: &str, data: &HashMap<String, String>) -> Result<U, WriteError> {
+        self.record("create_user", |manager| manager.create_user(name, data), |result| result.is_ok() as usize)
+    }
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> Result<I, WriteError> {
+        self.record("create_item", |manager| manager.create_item(name, data), |result| result.is_ok() as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic::{DynItem, DynUser};
+    use crate::DynId;
+
+    #[test]
+    fn report_orders_by_total_time() {
+        let mut report = QueryReport::default();
+        report.methods.insert("get_user_by_id", MethodStats{calls: 4, total: Duration::from_millis(8), max: Duration::from_millis(5), rows: 4});
+        report.methods.insert("get_all_users", MethodStats{calls: 1, total: Duration::from_millis(30), max: Duration::from_millis(30), rows: 610});
+
+        assert_eq!(report.methods["get_user_by_id"].mean(), Duration::from_millis(2));
+        assert_eq!(report.total(), Duration::from_millis(38));
+
+        let printed = report.to_string();
+        assert!(printed.find("get_all_users").unwrap() < printed.find("get_user_by_id").unwrap());
+        assert!(printed.ends_with("Total database time: 38.000 ms\n"));
+    }
+
+    /// Users with ratings in memory, `get_all_users` reads every user's ratings as the managers do.
+    /// Methods the test doesn't use return nothing and writes store nothing.
+    struct StubManager {
+        ratings: BTreeMap<i32, HashMap<DynId, f64>>
+    }
+
+    impl DBManager<DynUser, DynItem> for StubManager {
+        fn rating_scale(&self) -> RatingScale {
+            RatingScale::create(1.0, 5.0, Some(1.0))
+        }
+
+        fn get_user_by_name(&self, _: &str) -> Vec<DynUser> { Vec::new() }
+        fn get_user_by_id(&self, _: DynId) -> Vec<DynUser> { Vec::new() }
+        fn get_users_by_query(&self, _: &UserQuery) -> Vec<DynUser> { Vec::new() }
+        fn get_item_by_name(&self, _: &str) -> Vec<DynItem> { Vec::new() }
+        fn get_item_by_id(&self, _: DynId) -> Vec<DynItem> { Vec::new() }
+        fn get_users_by_ids(&self, _: &[DynId]) -> HashMap<DynId, DynUser> { HashMap::new() }
+        fn get_items_by_ids(&self, _: &[DynId]) -> HashMap<DynId, DynItem> { HashMap::new() }
+        fn search_items(&self, _: &str, _: MatchMode, _: usize) -> Vec<SearchResult<DynItem>> { Vec::new() }
+        fn get_all_users(&self) -> Vec<DynUser> {
+            self.ratings.keys().map(|&id| DynUser{
+                id: DynId::Int(id),
+                name: id.to_string(),
+                data: HashMap::new(),
+                ratings: self.get_user_ratings(DynId::Int(id))
+            }).collect()
+        }
+        fn get_all_ratings(&self) -> HashMap<DynId, HashMap<DynId, f64>> { HashMap::new() }
+        fn get_users_chunk(&self, _: Option<DynId>, _: i64) -> Vec<DynId> { Vec::new() }
+        fn get_items_chunk(&self, _: Option<DynId>, _: i64) -> Vec<DynItem> { Vec::new() }
+        fn get_user_ratings(&self, uid: DynId) -> HashMap<DynId, f64> {
+            match uid {
+                DynId::Int(id) => self.ratings.get(&id).cloned().unwrap_or_default(),
+                DynId::Text(_) => HashMap::new()
+            }
+        }
+        fn get_users_with_ratings_chunk(&self, _: Option<DynId>, _: i64) -> HashMap<DynId, HashMap<DynId, f64>> { HashMap::new() }
+        fn get_item_ratings(&self, _: DynId) -> HashMap<DynId, f64> { HashMap::new() }
+        fn get_items_with_ratings_chunk(&self, _: Option<DynId>, _: i64) -> HashMap<DynId, HashMap<DynId, f64>> { HashMap::new() }
+        fn get_item_rating_counts(&self) -> HashMap<DynId, i64> { HashMap::new() }
+        fn count_users(&self) -> i64 {
+            self.ratings.len() as i64
+        }
+        fn count_items(&self) -> i64 { 0 }
+        fn count_ratings(&self) -> i64 { 0 }
+        fn get_ratings_after(&self, _: i64, _: i64) -> Vec<RatingRow<DynId, DynId>> { Vec::new() }
+        fn get_all_ratings_of(&self, _: RatingKind) -> HashMap<DynId, HashMap<DynId, f64>> { HashMap::new() }
+        fn get_user_ratings_of(&self, _: DynId, _: RatingKind) -> HashMap<DynId, f64> { HashMap::new() }
+        fn add_rating(&self, _: DynId, _: DynId, _: f64) -> Result<(), WriteError> { Ok(()) }
+        fn delete_rating(&self, _: DynId, _: DynId) -> bool { false }
+        fn create_user(&self, _: &str, _: &HashMap<String, String>) -> Result<DynUser, WriteError> { Err(WriteError::InvalidData(String::from("the stub stores nothing"))) }
+        fn create_item(&self, _: &str, _: &HashMap<String, String>) -> Result<DynItem, WriteError> { Err(WriteError::InvalidData(String::from("the stub stores nothing"))) }
+    }
+
+    #[test]
+    fn calls_and_rows_are_counted() {
+        let ratings = BTreeMap::from([
+            (1, HashMap::from([(DynId::Int(10), 4.0), (DynId::Int(11), 2.0)])),
+            (2, HashMap::from([(DynId::Int(10), 5.0)])),
+            (3, HashMap::new())
+        ]);
+        let manager = InstrumentedManager::create(StubManager{ratings});
+
+        assert_eq!(DBManager::<DynUser, DynItem>::count_users(&manager), 3);
+        assert_eq!(manager.get_all_users().len(), 3);
+        assert_eq!(DBManager::<DynUser, DynItem>::get_user_ratings(&manager, DynId::Int(1)).len(), 2);
+        assert_eq!(DBManager::<DynUser, DynItem>::get_user_ratings(&manager, DynId::Int(2)).len(), 1);
+
+        assert!(DBManager::<DynUser, DynItem>::add_rating(&manager, DynId::Int(3), DynId::Int(10), 3.0).is_ok());
+        assert!(!DBManager::<DynUser, DynItem>::delete_rating(&manager, DynId::Int(3), DynId::Int(11)));
+
+        let report = manager.report();
+        assert_eq!(report.methods.len(), 5);
+        assert_eq!((report.methods["count_users"].calls, report.methods["count_users"].rows), (1, 1));
+        // The ratings it reads for every user are part of the one call
+        assert_eq!((report.methods["get_all_users"].calls, report.methods["get_all_users"].rows), (1, 3));
+        assert_eq!((report.methods["get_user_ratings"].calls, report.methods["get_user_ratings"].rows), (2, 3));
+        // Writes count the changes made
+        assert_eq!((report.methods["add_rating"].calls, report.methods["add_rating"].rows), (1, 1));
+        assert_eq!((report.methods["delete_rating"].calls, report.methods["delete_rating"].rows), (1, 0));
+        assert!(report.methods.values().all(|stats| stats.max <= stats.total));
+
+        manager.reset();
+        assert!(manager.report().methods.is_empty());
+    }
+}
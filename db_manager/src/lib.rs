@@ -1,17 +1,165 @@
 use std::collections::HashMap;
+use std::fmt::{self, Display};
 
-pub trait DBManager<U: User<I>, I: Item> {
+pub mod async_manager;
+pub mod cache;
+pub mod dynamic;
+pub mod import;
+pub mod instrument;
+pub mod location;
+pub mod memory;
+pub mod pool;
+pub mod scale;
+pub mod search;
+pub mod stream;
+
+pub use async_manager::{AsyncAdapter, AsyncDBManager};
+pub use cache::{CacheConfig, CachePolicy, CacheStats, CachingManager};
+pub use dynamic::{DynDBManager, DynId, Registry};
+pub use instrument::{InstrumentedManager, MethodStats, QueryReport};
+pub use memory::MemoryManager;
+pub use scale::{Normalization, Normalizer, RatingScale};
+pub use search::{MatchMode, SearchResult};
+pub use stream::{RatingRow, RatingsIter};
+
+/// Criteria for `DBManager::get_users_by_query`, every criterion set must hold.
+/// Criteria over attributes a dataset doesn't have match no users.
+#[derive(Debug, Clone, Default)]
+pub struct UserQuery {
+    pub name: Option<String>,
+    /// Case-insensitive part of the name of the user's city.
+    pub city: Option<String>,
+    /// Case-insensitive part of the name of the user's state or region.
+    pub state: Option<String>,
+    /// Name of the user's country, any spelling known by `location::normalize_country`.
+    pub country: Option<String>,
+    pub min_age: Option<i32>,
+    pub max_age: Option<i32>,
+    pub min_ratings: Option<i64>,
+    /// Users are ordered by id, so `offset` and `limit` page through them.
+    pub offset: Option<i64>,
+    pub limit: Option<i64>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RatingKind {
+    /// A score given by the user.
+    Explicit,
+    /// An interaction without a score, e.g. a Book-Crossing rating of 0.
+    Implicit
+}
+
+/// Explicit and implicit feedback kept as separate signals.
+#[derive(Debug, Clone)]
+pub struct Signals<UID, IID> {
+    pub explicit: HashMap<UID, HashMap<IID, f64>>,
+    pub implicit: HashMap<UID, HashMap<IID, f64>>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteError {
+    /// The rating isn't a valid value of the dataset's `rating_scale`.
+    OutOfScale(f64),
+    UnknownUser,
+    UnknownItem,
+    /// An attribute is missing or can't be stored, e.g. an age that isn't a number.
+    InvalidData(String),
+    /// The database failed to store the change.
+    Database(String)
+}
+
+impl From<diesel::result::Error> for WriteError {
+    fn from(error: diesel::result::Error) -> WriteError {
+        WriteError::Database(error.to_string())
+    }
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WriteError::OutOfScale(rating) => write!(f, "The rating {} is out of the dataset's scale", rating),
+            WriteError::UnknownUser => write!(f, "There is no user with the given id"),
+            WriteError::UnknownItem => write!(f, "There is no item with the given id"),
+            WriteError::InvalidData(reason) => write!(f, "Invalid data: {}", reason),
+            WriteError::Database(reason) => write!(f, "The database failed to store it: {}", reason)
+        }
+    }
+}
+
+/// Managers connected to a database by its URL. Managers built some other way, e.g. boxed by
+/// a `Registry`, implement only `DBManager`.
+pub trait Connect {
     fn connect_to(url: &str) -> Self;
+}
+
+pub trait DBManager<U: User<I>, I: Item> {
+    /// Scale of the explicit ratings.
+    fn rating_scale(&self) -> RatingScale;
 
     fn get_user_by_name(&self, name: &str) -> Vec<U>;
     fn get_user_by_id(&self, uid: U::ID) -> Vec<U>;
+    fn get_users_by_query(&self, query: &UserQuery) -> Vec<U>;
     fn get_item_by_name(&self, name: &str) -> Vec<I>;
     fn get_item_by_id(&self, uid: I::ID) -> Vec<I>;
+    /// Users with the given ids in one query, ids not found are left out.
+    fn get_users_by_ids(&self, uids: &[U::ID]) -> HashMap<U::ID, U>;
+    /// Items with the given ids in one query, ids not found are left out.
+    fn get_items_by_ids(&self, iids: &[I::ID]) -> HashMap<I::ID, I>;
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> Vec<SearchResult<I>>;
     fn get_all_users(&self) -> Vec<U>;
     fn get_all_ratings(&self) -> HashMap<U::ID, HashMap<I::ID, f64>>;
-    fn get_users_chunk(&self, offset: i64, limit: i64) -> Vec<U::ID>;
+    /// Up to `limit` user ids greater than `after`, ordered by id. Pass the last id of a chunk
+    /// to get the next one, `None` starts from the first user.
+    fn get_users_chunk(&self, after: Option<U::ID>, limit: i64) -> Vec<U::ID>;
+    /// Up to `limit` items with ids greater than `after`, ordered by id. Pass the last id of a chunk
+    /// to get the next one, `None` starts from the first item.
+    fn get_items_chunk(&self, after: Option<I::ID>, limit: i64) -> Vec<I>;
     fn get_user_ratings(&self, uid: U::ID) -> HashMap<I::ID, f64>;
-    fn get_users_with_ratings_chunk(&self, offset: i64, limit: i64) -> HashMap<U::ID, HashMap<I::ID, f64>>;
+    /// Ratings of the users of `get_users_chunk` with the same arguments, users without ratings are left out.
+    fn get_users_with_ratings_chunk(&self, after: Option<U::ID>, limit: i64) -> HashMap<U::ID, HashMap<I::ID, f64>>;
+    /// Explicit ratings the item received, by user.
+    fn get_item_ratings(&self, iid: I::ID) -> HashMap<U::ID, f64>;
+    /// Ratings received by the items of `get_items_chunk` with the same arguments, items without ratings are left out.
+    fn get_items_with_ratings_chunk(&self, after: Option<I::ID>, limit: i64) -> HashMap<I::ID, HashMap<U::ID, f64>>;
+    /// Explicit ratings each item received, items without ratings are left out.
+    fn get_item_rating_counts(&self) -> HashMap<I::ID, i64>;
+    fn count_users(&self) -> i64;
+    fn count_items(&self) -> i64;
+    /// Explicit ratings only.
+    fn count_ratings(&self) -> i64;
+    /// Up to `limit` explicit ratings with a key greater than `after`, ordered by key.
+    fn get_ratings_after(&self, after: i64, limit: i64) -> Vec<RatingRow<U::ID, I::ID>>;
+
+    /// Streams every explicit rating, `batch_size` of them per query, instead of loading them all
+    /// like `get_all_ratings`.
+    fn ratings_iter(&self, batch_size: i64) -> RatingsIter<'_, Self, U, I>
+    where
+        Self: Sized
+    {
+        RatingsIter::create(self, batch_size)
+    }
+
+    /// Ratings of the given kind only, datasets without implicit feedback have no implicit ratings.
+    /// The other rating methods only return explicit ratings.
+    fn get_all_ratings_of(&self, kind: RatingKind) -> HashMap<U::ID, HashMap<I::ID, f64>>;
+    fn get_user_ratings_of(&self, uid: U::ID, kind: RatingKind) -> HashMap<I::ID, f64>;
+
+    fn get_all_signals(&self) -> Signals<U::ID, I::ID> {
+        Signals{
+            explicit: self.get_all_ratings_of(RatingKind::Explicit),
+            implicit: self.get_all_ratings_of(RatingKind::Implicit)
+        }
+    }
+
+    /// Stores an explicit rating, replacing the user's previous rating of the item.
+    fn add_rating(&self, uid: U::ID, iid: I::ID, rating: f64) -> Result<(), WriteError>;
+    /// Deletes the explicit rating, implicit interactions are kept. Returns false if the user hadn't rated the item.
+    fn delete_rating(&self, uid: U::ID, iid: I::ID) -> bool;
+    /// Creates a user without ratings. `data` takes the keys of `User::data`, the name is ignored
+    /// by datasets whose users have none.
+    fn create_user(&self, name: &str, data: &HashMap<String, String>) -> Result<U, WriteError>;
+    /// Creates an item named `name`, `data` takes the keys of `Item::data`.
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> Result<I, WriteError>;
 }
 
 pub trait User<I: Item> {
@@ -0,0 +1,120 @@
+/// Free-text location split into its parts, e.g. Book-Crossing's "nyc, new york, usa".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Location {
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>
+}
+
+/// Spellings of a country found in user-entered locations, mapped to the name stored.
+const COUNTRY_ALIASES: &[(&str, &str)] = &[
+    ("us", "usa"),
+    ("u.s", "usa"),
+    ("u.s.a", "usa"),
+    ("united states", "usa"),
+    ("united states of america", "usa"),
+    ("america", "usa"),
+    ("uk", "united kingdom"),
+    ("u.k", "united kingdom"),
+    ("great britain", "united kingdom"),
+    ("england", "united kingdom"),
+    ("scotland", "united kingdom"),
+    ("wales", "united kingdom"),
+    ("northern ireland", "united kingdom"),
+    ("deutschland", "germany"),
+    ("españa", "spain"),
+    ("espana", "spain"),
+    ("italia", "italy"),
+    ("österreich", "austria"),
+    ("schweiz", "switzerland"),
+    ("suisse", "switzerland"),
+    ("brasil", "brazil"),
+    ("méxico", "mexico"),
+    ("nederland", "netherlands"),
+    ("the netherlands", "netherlands"),
+    ("holland", "netherlands"),
+];
+
+/// Countries recognized when a location is made only of a country name.
+const COUNTRIES: &[&str] = &[
+    "usa", "canada", "united kingdom", "germany", "spain", "italy", "austria", "switzerland",
+    "brazil", "mexico", "netherlands", "france", "portugal", "australia", "new zealand",
+    "malaysia", "japan", "china", "india", "russia", "ireland", "belgium", "sweden", "norway",
+    "denmark", "finland", "argentina", "singapore", "philippines", "south africa",
+];
+
+/// Splits a "city, state, country" location. Two parts are a city and a country, more than three
+/// keep the extra parts in the state, and a single part is a country only if it's a known one.
+pub fn parse_location(raw: &str) -> Location {
+    let parts: Vec<&str> = raw.split(',').map(str::trim).collect();
+    let part = |value: &str| if is_missing(value) { None } else { Some(String::from(value)) };
+
+    match parts.as_slice() {
+        [single] => {
+            match normalize_country(single) {
+                Some(country) if is_known_country(&country) => Location{country: Some(country), ..Default::default()},
+                _ => Location{city: part(single), ..Default::default()}
+            }
+        }
+        [city, country] => Location{city: part(city), state: None, country: normalize_country(country)},
+        [city, states @ .., country] => {
+            let states: Vec<&str> = states.iter().copied().filter(|state| !is_missing(state)).collect();
+            Location{
+                city: part(city),
+                state: if states.is_empty() { None } else { Some(states.join(", ")) },
+                country: normalize_country(country)
+            }
+        }
+        [] => Location::default()
+    }
+}
+
+/// Lowercases a country name and maps its known spellings to a single one, `None` if it's missing.
+pub fn normalize_country(country: &str) -> Option<String> {
+    let country = country.trim().trim_matches(|c: char| c == '"' || c == '\'' || c == '.').trim();
+    if is_missing(country) {
+        return None;
+    }
+
+    let country = country.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    let normalized = COUNTRY_ALIASES.iter()
+        .find(|(alias, _)| *alias == country)
+        .map(|(_, name)| String::from(*name));
+
+    Some(normalized.unwrap_or(country))
+}
+
+fn is_known_country(country: &str) -> bool {
+    COUNTRIES.contains(&country)
+}
+
+fn is_missing(value: &str) -> bool {
+    matches!(value.to_lowercase().as_str(), "" | "n/a" | "na" | "-" | "?" | "none" | "unknown")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_locations() {
+        assert_eq!(parse_location("nyc, new york, usa"), Location{
+            city: Some(String::from("nyc")),
+            state: Some(String::from("new york")),
+            country: Some(String::from("usa"))
+        });
+        assert_eq!(parse_location("porto, n/a, portugal"), Location{
+            city: Some(String::from("porto")),
+            state: None,
+            country: Some(String::from("portugal"))
+        });
+        assert_eq!(parse_location("london, England."), Location{
+            city: Some(String::from("london")),
+            state: None,
+            country: Some(String::from("united kingdom"))
+        });
+        assert_eq!(parse_location("Deutschland").country.as_deref(), Some("germany"));
+        assert_eq!(parse_location("springfield").city.as_deref(), Some("springfield"));
+        assert_eq!(parse_location(", , "), Location::default());
+    }
+}
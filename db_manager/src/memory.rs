@@ -0,0 +1,461 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+
+use futures::future::{self, BoxFuture};
+use futures::stream::{self, BoxStream};
+
+use crate::async_manager::{AsyncDBManager, RatingsByItem, RatingsByUser, UserRatings};
+use crate::dynamic::{DynId, DynItem, DynUser};
+use crate::{search, DBManager, UserQuery, RatingKind, RatingRow, RatingScale, MatchMode, SearchResult, Signals, WriteError};
+
+/// Name and attributes of a user or item.
+struct Entity {
+    name: String,
+    data: HashMap<String, String>
+}
+
+struct Rating {
+    user: i32,
+    item: i32,
+    rating: f64
+}
+
+#[derive(Default)]
+struct Store {
+    users: BTreeMap<i32, Entity>,
+    items: BTreeMap<i32, Entity>,
+    /// Ratings by key, a rating that replaces another one keeps its key.
+    ratings: BTreeMap<i64, Rating>,
+    last_key: i64
+}
+
+impl Store {
+    fn user(&self, id: i32) -> Option<DynUser> {
+        self.users.get(&id).map(|user| DynUser{
+            id: DynId::Int(id),
+            name: user.name.clone(),
+            data: user.data.clone(),
+            ratings: self.user_ratings(id)
+        })
+    }
+
+    fn item(&self, id: i32) -> Option<DynItem> {
+        self.items.get(&id).map(|item| DynItem{id: DynId::Int(id), name: item.name.clone(), data: item.data.clone()})
+    }
+
+    fn user_ratings(&self, id: i32) -> HashMap<DynId, f64> {
+        self.ratings.values().filter(|rating| rating.user == id).map(|rating| (DynId::Int(rating.item), rating.rating)).collect()
+    }
+
+    fn item_ratings(&self, id: i32) -> HashMap<DynId, f64> {
+        self.ratings.values().filter(|rating| rating.item == id).map(|rating| (DynId::Int(rating.user), rating.rating)).collect()
+    }
+
+    fn ratings_by_user(&self) -> RatingsByUser<DynId, DynId> {
+        let mut ratings_by_user = HashMap::new();
+        for rating in self.ratings.values() {
+            ratings_by_user.entry(DynId::Int(rating.user)).or_insert_with(HashMap::new).insert(DynId::Int(rating.item), rating.rating);
+        }
+        ratings_by_user
+    }
+
+    fn rating_key(&self, user: i32, item: i32) -> Option<i64> {
+        self.ratings.iter().find(|(_, rating)| rating.user == user && rating.item == item).map(|(key, _)| *key)
+    }
+}
+
+/// Ids of the store are integers, other ids match nothing.
+fn int_id(id: &DynId) -> Option<i32> {
+    match id {
+        DynId::Int(id) => Some(*id),
+        DynId::Text(_) => None
+    }
+}
+
+/// Ids greater than `after`, `None` if `after` is of another kind so there is nothing after it.
+fn start_after(after: Option<DynId>) -> Option<i32> {
+    match after {
+        Some(after) => int_id(&after).map(|after| after.saturating_add(1)),
+        None => Some(i32::MIN)
+    }
+}
+
+/// Dataset kept in memory, a stand-in for the Postgres managers in tests and examples. Users and
+/// items get consecutive ids as they are created and their ratings are explicit.
+pub struct MemoryManager {
+    scale: RatingScale,
+    store: Mutex<Store>
+}
+
+impl MemoryManager {
+    pub fn create(scale: RatingScale) -> MemoryManager {
+        MemoryManager{scale, store: Mutex::new(Store::default())}
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().expect("A thread panicked while using the store")
+    }
+}
+
+impl DBManager<DynUser, DynItem> for MemoryManager {
+    fn rating_scale(&self) -> RatingScale {
+        self.scale
+    }
+
+    fn get_user_by_name(&self, name: &str) -> Vec<DynUser> {
+        DBManager::get_users_by_query(self, &UserQuery{name: Some(String::from(name)), ..Default::default()})
+    }
+    fn get_user_by_id(&self, uid: DynId) -> Vec<DynUser> {
+        int_id(&uid).and_then(|uid| self.lock().user(uid)).into_iter().collect()
+    }
+    /// Users have no location or age, criteria over them match no users.
+    fn get_users_by_query(&self, query: &UserQuery) -> Vec<DynUser> {
+        if query.city.is_some() || query.state.is_some() || query.country.is_some() || query.min_age.is_some() || query.max_age.is_some() {
+            return Vec::new();
+        }
+
+        let store = self.lock();
+        store.users.iter()
+            .filter(|(_, user)| query.name.as_ref().is_none_or(|name| user.name == *name))
+            .filter_map(|(id, _)| store.user(*id))
+            .filter(|user| query.min_ratings.is_none_or(|min_ratings| user.ratings.len() as i64 >= min_ratings))
+            .skip(query.offset.unwrap_or(0).max(0) as usize)
+            .take(query.limit.map_or(usize::MAX, |limit| limit.max(0) as usize))
+            .collect()
+    }
+    fn get_item_by_name(&self, name: &str) -> Vec<DynItem> {
+        let store = self.lock();
+        store.items.iter().filter(|(_, item)| item.name == name).filter_map(|(id, _)| store.item(*id)).collect()
+    }
+    fn get_item_by_id(&self, iid: DynId) -> Vec<DynItem> {
+        int_id(&iid).and_then(|iid| self.lock().item(iid)).into_iter().collect()
+    }
+    fn get_users_by_ids(&self, uids: &[DynId]) -> HashMap<DynId, DynUser> {
+        let store = self.lock();
+        uids.iter().filter_map(|uid| int_id(uid).and_then(|id| store.user(id))).map(|user| (user.id.clone(), user)).collect()
+    }
+    fn get_items_by_ids(&self, iids: &[DynId]) -> HashMap<DynId, DynItem> {
+        let store = self.lock();
+        iids.iter().filter_map(|iid| int_id(iid).and_then(|id| store.item(id))).map(|item| (item.id.clone(), item)).collect()
+    }
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> Vec<SearchResult<DynItem>> {
+        let store = self.lock();
+        let candidates = store.items.keys().filter_map(|id| store.item(*id)).map(|item| {
+            let name = item.name.clone();
+            (item, name)
+        }).collect();
+
+        search::rank(query, mode, candidates, limit).into_iter().map(|(item, score)| SearchResult{item, score}).collect()
+    }
+    fn get_all_users(&self) -> Vec<DynUser> {
+        let store = self.lock();
+        store.users.keys().filter_map(|id| store.user(*id)).collect()
+    }
+    fn get_all_ratings(&self) -> RatingsByUser<DynId, DynId> {
+        self.lock().ratings_by_user()
+    }
+    fn get_users_chunk(&self, after: Option<DynId>, limit: i64) -> Vec<DynId> {
+        let from = match start_after(after) {
+            Some(from) => from,
+            None => return Vec::new()
+        };
+        self.lock().users.range(from..).take(limit.max(0) as usize).map(|(id, _)| DynId::Int(*id)).collect()
+    }
+    fn get_items_chunk(&self, after: Option<DynId>, limit: i64) -> Vec<DynItem> {
+        let from = match start_after(after) {
+            Some(from) => from,
+            None => return Vec::new()
+        };
+        let store = self.lock();
+        store.items.range(from..).take(limit.max(0) as usize).filter_map(|(id, _)| store.item(*id)).collect()
+    }
+    fn get_user_ratings(&self, uid: DynId) -> HashMap<DynId, f64> {
+        int_id(&uid).map(|uid| self.lock().user_ratings(uid)).unwrap_or_default()
+    }
+    fn get_users_with_ratings_chunk(&self, after: Option<DynId>, limit: i64) -> RatingsByUser<DynId, DynId> {
+        let users = DBManager::get_users_chunk(self, after, limit);
+        let store = self.lock();
+        users.into_iter()
+            .filter_map(|uid| int_id(&uid).map(|id| (uid, store.user_ratings(id))))
+            .filter(|(_, ratings)| !ratings.is_empty())
+            .collect()
+    }
+    fn get_item_ratings(&self, iid: DynId) -> HashMap<DynId, f64> {
+        int_id(&iid).map(|iid| self.lock().item_ratings(iid)).unwrap_or_default()
+    }
+    fn get_items_with_ratings_chunk(&self, after: Option<DynId>, limit: i64) -> RatingsByItem<DynId, DynId> {
+        let items = DBManager::get_items_chunk(self, after, limit);
+        let store = self.lock();
+        items.into_iter()
+            .filter_map(|item| int_id(&item.id).map(|id| (item.id, store.item_ratings(id))))
+            .filter(|(_, ratings)| !ratings.is_empty())
+            .collect()
+    }
+    fn get_item_rating_counts(&self) -> HashMap<DynId, i64> {
+        let mut counts = HashMap::new();
+        for rating in self.lock().ratings.values() {
+            *counts.entry(DynId::Int(rating.item)).or_insert(0) += 1;
+        }
+        counts
+    }
+    fn count_users(&self) -> i64 {
+        self.lock().users.len() as i64
+    }
+    fn count_items(&self) -> i64 {
+        self.lock().items.len() as i64
+    }
+    fn count_ratings(&self) -> i64 {
+        self.lock().ratings.len() as i64
+    }
+    fn get_ratings_after(&self, after: i64, limit: i64) -> Vec<RatingRow<DynId, DynId>> {
+        let store = self.lock();
+        store.ratings.range(after.saturating_add(1)..)
+            .take(limit.max(0) as usize)
+            .map(|(key, rating)| RatingRow{key: *key, user: DynId::Int(rating.user), item: DynId::Int(rating.item), rating: rating.rating})
+            .collect()
+    }
+    fn get_all_ratings_of(&self, kind: RatingKind) -> RatingsByUser<DynId, DynId> {
+        match kind {
+            RatingKind::Explicit => DBManager::get_all_ratings(self),
+            RatingKind::Implicit => HashMap::new()
+        }
+    }
+    fn get_user_ratings_of(&self, uid: DynId, kind: RatingKind) -> HashMap<DynId, f64> {
+        match kind {
+            RatingKind::Explicit => DBManager::get_user_ratings(self, uid),
+            RatingKind::Implicit => HashMap::new()
+        }
+    }
+
+    fn add_rating(&self, uid: DynId, iid: DynId, rating: f64) -> Result<(), WriteError> {
+        if !self.scale.contains(rating) {
+            return Err(WriteError::OutOfScale(rating));
+        }
+
+        let mut store = self.lock();
+        let user = int_id(&uid).filter(|user| store.users.contains_key(user)).ok_or(WriteError::UnknownUser)?;
+        let item = int_id(&iid).filter(|item| store.items.contains_key(item)).ok_or(WriteError::UnknownItem)?;
+
+        let key = match store.rating_key(user, item) {
+            Some(key) => key,
+            None => {
+                store.last_key += 1;
+                store.last_key
+            }
+        };
+        store.ratings.insert(key, Rating{user, item, rating});
+        Ok(())
+    }
+    fn delete_rating(&self, uid: DynId, iid: DynId) -> bool {
+        let (user, item) = match (int_id(&uid), int_id(&iid)) {
+            (Some(user), Some(item)) => (user, item),
+            _ => return false
+        };
+
+        let mut store = self.lock();
+        match store.rating_key(user, item) {
+            Some(key) => store.ratings.remove(&key).is_some(),
+            None => false
+        }
+    }
+    fn create_user(&self, name: &str, data: &HashMap<String, String>) -> Result<DynUser, WriteError> {
+        let mut store = self.lock();
+        let id = store.users.keys().next_back().map_or(1, |last| last + 1);
+        store.users.insert(id, Entity{name: String::from(name), data: data.clone()});
+        Ok(store.user(id).expect("The user was just created"))
+    }
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> Result<DynItem, WriteError> {
+        let mut store = self.lock();
+        let id = store.items.keys().next_back().map_or(1, |last| last + 1);
+        store.items.insert(id, Entity{name: String::from(name), data: data.clone()});
+        Ok(store.item(id).expect("The item was just created"))
+    }
+}
+
+fn ready<T: Send + 'static>(value: T) -> BoxFuture<'static, T> {
+    Box::pin(future::ready(value))
+}
+
+/// Nothing blocks in memory, so the calls run right away and their futures are ready, without
+/// the blocking threads `AsyncAdapter` needs.
+impl AsyncDBManager<DynUser, DynItem> for MemoryManager {
+    fn rating_scale(&self) -> RatingScale {
+        self.scale
+    }
+
+    fn get_user_by_name(&self, name: &str) -> BoxFuture<'static, Vec<DynUser>> {
+        ready(DBManager::get_user_by_name(self, name))
+    }
+    fn get_user_by_id(&self, uid: DynId) -> BoxFuture<'static, Vec<DynUser>> {
+        ready(DBManager::get_user_by_id(self, uid))
+    }
+    fn get_users_by_query(&self, query: &UserQuery) -> BoxFuture<'static, Vec<DynUser>> {
+        ready(DBManager::get_users_by_query(self, query))
+    }
+    fn get_item_by_name(&self, name: &str) -> BoxFuture<'static, Vec<DynItem>> {
+        ready(DBManager::get_item_by_name(self, name))
+    }
+    fn get_item_by_id(&self, iid: DynId) -> BoxFuture<'static, Vec<DynItem>> {
+        ready(DBManager::get_item_by_id(self, iid))
+    }
+    fn get_users_by_ids(&self, uids: &[DynId]) -> BoxFuture<'static, HashMap<DynId, DynUser>> {
+        ready(DBManager::get_users_by_ids(self, uids))
+    }
+    fn get_items_by_ids(&self, iids: &[DynId]) -> BoxFuture<'static, HashMap<DynId, DynItem>> {
+        ready(DBManager::get_items_by_ids(self, iids))
+    }
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> BoxFuture<'static, Vec<SearchResult<DynItem>>> {
+        ready(DBManager::search_items(self, query, mode, limit))
+    }
+    fn get_all_users(&self) -> BoxFuture<'static, Vec<DynUser>> {
+        ready(DBManager::get_all_users(self))
+    }
+    fn get_all_ratings(&self) -> BoxFuture<'static, RatingsByUser<DynId, DynId>> {
+        ready(DBManager::get_all_ratings(self))
+    }
+    fn get_users_chunk(&self, after: Option<DynId>, limit: i64) -> BoxFuture<'static, Vec<DynId>> {
+        ready(DBManager::get_users_chunk(self, after, limit))
+    }
+    fn get_items_chunk(&self, after: Option<DynId>, limit: i64) -> BoxFuture<'static, Vec<DynItem>> {
+        ready(DBManager::get_items_chunk(self, after, limit))
+    }
+    fn get_user_ratings(&self, uid: DynId) -> BoxFuture<'static, HashMap<DynId, f64>> {
+        ready(DBManager::get_user_ratings(self, uid))
+    }
+    fn get_users_with_ratings_chunk(&self, after: Option<DynId>, limit: i64) -> BoxFuture<'static, RatingsByUser<DynId, DynId>> {
+        ready(DBManager::get_users_with_ratings_chunk(self, after, limit))
+    }
+    fn get_item_ratings(&self, iid: DynId) -> BoxFuture<'static, HashMap<DynId, f64>> {
+        ready(DBManager::get_item_ratings(self, iid))
+    }
+    fn get_items_with_ratings_chunk(&self, after: Option<DynId>, limit: i64) -> BoxFuture<'static, RatingsByItem<DynId, DynId>> {
+        ready(DBManager::get_items_with_ratings_chunk(self, after, limit))
+    }
+    fn get_item_rating_counts(&self) -> BoxFuture<'static, HashMap<DynId, i64>> {
+        ready(DBManager::get_item_rating_counts(self))
+    }
+    fn count_users(&self) -> BoxFuture<'static, i64> {
+        ready(DBManager::count_users(self))
+    }
+    fn count_items(&self) -> BoxFuture<'static, i64> {
+        ready(DBManager::count_items(self))
+    }
+    fn count_ratings(&self) -> BoxFuture<'static, i64> {
+        ready(DBManager::count_ratings(self))
+    }
+    fn get_ratings_after(&self, after: i64, limit: i64) -> BoxFuture<'static, Vec<RatingRow<DynId, DynId>>> {
+        ready(DBManager::get_ratings_after(self, after, limit))
+    }
+    fn get_all_ratings_of(&self, kind: RatingKind) -> BoxFuture<'static, RatingsByUser<DynId, DynId>> {
+        ready(DBManager::get_all_ratings_of(self, kind))
+    }
+    fn get_user_ratings_of(&self, uid: DynId, kind: RatingKind) -> BoxFuture<'static, HashMap<DynId, f64>> {
+        ready(DBManager::get_user_ratings_of(self, uid, kind))
+    }
+    fn get_all_signals(&self) -> BoxFuture<'static, Signals<DynId, DynId>> {
+        ready(DBManager::get_all_signals(self))
+    }
+
+    /// Every chunk of `chunk_size` users is read right away, the users come ordered by id.
+    fn ratings_stream(&self, chunk_size: i64) -> BoxStream<'static, UserRatings<DynId, DynId>> {
+        let mut ratings = Vec::new();
+        let mut after = None;
+        loop {
+            let users = DBManager::get_users_chunk(self, after.clone(), chunk_size);
+            if users.is_empty() {
+                break;
+            }
+
+            let mut chunk = DBManager::get_users_with_ratings_chunk(self, after, chunk_size);
+            ratings.extend(users.iter().filter_map(|user| chunk.remove_entry(user)));
+            after = users.last().cloned();
+        }
+        Box::pin(stream::iter(ratings))
+    }
+
+    fn add_rating(&self, uid: DynId, iid: DynId, rating: f64) -> BoxFuture<'static, Result<(), WriteError>> {
+        ready(DBManager::add_rating(self, uid, iid, rating))
+    }
+    fn delete_rating(&self, uid: DynId, iid: DynId) -> BoxFuture<'static, bool> {
+        ready(DBManager::delete_rating(self, uid, iid))
+    }
+    fn create_user(&self, name: &str, data: &HashMap<String, String>) -> BoxFuture<'static, Result<DynUser, WriteError>> {
+        ready(DBManager::create_user(self, name, data))
+    }
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> BoxFuture<'static, Result<DynItem, WriteError>> {
+        ready(DBManager::create_item(self, name, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use futures::StreamExt;
+    use crate::AsyncAdapter;
+
+    fn fixture() -> MemoryManager {
+        let manager = MemoryManager::create(RatingScale::create(1.0, 5.0, Some(1.0)));
+        let no_data = HashMap::new();
+        for name in ["Ana", "Chris", "Chris", "Dana"] {
+            DBManager::create_user(&manager, name, &no_data).unwrap();
+        }
+        for name in ["Alien", "Avatar", "Jaws"] {
+            DBManager::create_item(&manager, name, &no_data).unwrap();
+        }
+        for (user, item, rating) in [(1, 1, 4.0), (1, 2, 5.0), (2, 2, 3.0), (3, 3, 1.0), (2, 2, 2.0)] {
+            DBManager::add_rating(&manager, DynId::Int(user), DynId::Int(item), rating).unwrap();
+        }
+        manager
+    }
+
+    fn ids<T>(values: &[T], id: impl Fn(&T) -> DynId) -> Vec<DynId> {
+        values.iter().map(id).collect()
+    }
+
+    #[test]
+    fn memory_manager() {
+        let manager = fixture();
+        assert_eq!(DBManager::count_ratings(&manager), 4);
+        assert_eq!(DBManager::get_user_ratings(&manager, DynId::Int(2)), HashMap::from([(DynId::Int(2), 2.0)]));
+        assert_eq!(ids(&DBManager::get_user_by_name(&manager, "Chris"), |user| user.id.clone()), vec![DynId::Int(2), DynId::Int(3)]);
+        assert_eq!(DBManager::get_users_chunk(&manager, Some(DynId::Int(1)), 2), vec![DynId::Int(2), DynId::Int(3)]);
+        assert!(DBManager::get_users_chunk(&manager, Some(DynId::Text(String::from("1"))), 2).is_empty());
+
+        // A replaced rating keeps its key, so streaming by key doesn't see it twice
+        let keys: Vec<i64> = DBManager::get_ratings_after(&manager, 0, 10).iter().map(|row| row.key).collect();
+        assert_eq!(keys, vec![1, 2, 3, 4]);
+
+        assert_eq!(DBManager::add_rating(&manager, DynId::Int(1), DynId::Int(3), 6.0), Err(WriteError::OutOfScale(6.0)));
+        assert_eq!(DBManager::add_rating(&manager, DynId::Int(9), DynId::Int(3), 3.0), Err(WriteError::UnknownUser));
+        assert!(DBManager::delete_rating(&manager, DynId::Int(3), DynId::Int(3)));
+        assert!(!DBManager::delete_rating(&manager, DynId::Int(3), DynId::Int(3)));
+    }
+
+    #[test]
+    fn async_matches_sync() {
+        let manager = fixture();
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let adapter = AsyncAdapter::create(fixture());
+
+        let chris = block_on(AsyncDBManager::get_user_by_name(&manager, "Chris"));
+        assert_eq!(ids(&chris, |user| user.id.clone()), ids(&DBManager::get_user_by_name(&manager, "Chris"), |user| user.id.clone()));
+        assert_eq!(block_on(AsyncDBManager::get_all_ratings(&manager)), DBManager::get_all_ratings(&manager));
+        assert_eq!(block_on(AsyncDBManager::get_item_rating_counts(&manager)), DBManager::get_item_rating_counts(&manager));
+        assert_eq!(block_on(AsyncDBManager::get_ratings_after(&manager, 1, 2)), DBManager::get_ratings_after(&manager, 1, 2));
+        let searched = block_on(AsyncDBManager::search_items(&manager, "avtar", MatchMode::Fuzzy, 2));
+        assert_eq!(ids(&searched, |result| result.item.id.clone()), ids(&DBManager::search_items(&manager, "avtar", MatchMode::Fuzzy, 2), |result| result.item.id.clone()));
+
+        // The ready futures give what the adapter gives on its blocking threads
+        let streamed: Vec<UserRatings<DynId, DynId>> = block_on(AsyncDBManager::ratings_stream(&manager, 2).collect());
+        let mut adapted: Vec<UserRatings<DynId, DynId>> = runtime.block_on(AsyncDBManager::<DynUser, DynItem>::ratings_stream(&adapter, 2).collect());
+        // The adapter streams the users of a chunk in no particular order
+        adapted.sort_by(|first, second| first.0.cmp(&second.0));
+        assert_eq!(streamed, adapted);
+        assert_eq!(ids(&streamed, |(user, _)| user.clone()), vec![DynId::Int(1), DynId::Int(2), DynId::Int(3)]);
+
+        let written = block_on(AsyncDBManager::add_rating(&manager, DynId::Int(4), DynId::Int(1), 2.0));
+        let adapted = runtime.block_on(AsyncDBManager::<DynUser, DynItem>::add_rating(&adapter, DynId::Int(4), DynId::Int(1), 2.0));
+        assert_eq!((written, block_on(AsyncDBManager::count_ratings(&manager))), (adapted, runtime.block_on(AsyncDBManager::<DynUser, DynItem>::count_ratings(&adapter))));
+    }
+}
@@ -0,0 +1,17 @@
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, Pool, PooledConnection};
+
+/// Most connections a manager keeps open, shared by the threads using it.
+pub const POOL_SIZE: u32 = 8;
+
+pub type PgPool = Pool<ConnectionManager<PgConnection>>;
+pub type PgPooledConnection = PooledConnection<ConnectionManager<PgConnection>>;
+
+/// Opens one connection right away, so a wrong URL fails here, and the rest as they are needed.
+pub fn connect_pool(url: &str) -> PgPool {
+    Pool::builder()
+        .max_size(POOL_SIZE)
+        .min_idle(Some(1))
+        .build(ConnectionManager::new(url))
+        .expect("Failed connection to database. Maybe the URL?")
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Valid values of a dataset's explicit ratings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RatingScale {
+    pub min: f64,
+    pub max: f64,
+    /// Distance between consecutive valid ratings, e.g. 0.5 for MovieLens. `None` if any value is valid.
+    pub step: Option<f64>
+}
+
+impl RatingScale {
+    pub fn create(min: f64, max: f64, step: Option<f64>) -> RatingScale {
+        RatingScale{min, max, step}
+    }
+
+    pub fn contains(&self, rating: f64) -> bool {
+        if rating < self.min || rating > self.max {
+            return false;
+        }
+
+        match self.step {
+            Some(step) => {
+                let steps = (rating - self.min)/step;
+                (steps - steps.round()).abs() < 1e-9
+            }
+            None => true
+        }
+    }
+
+    pub fn clamp(&self, rating: f64) -> f64 {
+        rating.max(self.min).min(self.max)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    None,
+    /// Maps the rating scale to [0, 1], the same for every user.
+    MinMax,
+    /// Subtracts the mean rating of the user.
+    MeanCentering,
+    /// Subtracts the mean rating of the user and divides by their standard deviation.
+    ZScore
+}
+
+/// Normalization fitted to the ratings of one user, so predictions can be mapped back to their scale.
+#[derive(Debug, Clone, Copy)]
+pub struct Normalizer {
+    pub method: Normalization,
+    pub scale: RatingScale,
+    pub mean: f64,
+    pub std_dev: f64
+}
+
+impl Normalizer {
+    pub fn fit<I>(method: Normalization, scale: RatingScale, ratings: &HashMap<I, f64>) -> Normalizer {
+        let n = ratings.len().max(1) as f64;
+        let mean = ratings.values().sum::<f64>()/n;
+        let variance = ratings.values().map(|rating| (rating - mean).powi(2)).sum::<f64>()/n;
+
+        Normalizer{method, scale, mean, std_dev: variance.sqrt()}
+    }
+
+    pub fn normalize(&self, rating: f64) -> f64 {
+        match self.method {
+            Normalization::None => rating,
+            Normalization::MinMax => (rating - self.scale.min)/(self.scale.max - self.scale.min),
+            Normalization::MeanCentering => rating - self.mean,
+            Normalization::ZScore => (rating - self.mean)/self.spread()
+        }
+    }
+
+    pub fn denormalize(&self, value: f64) -> f64 {
+        match self.method {
+            Normalization::None => value,
+            Normalization::MinMax => value*(self.scale.max - self.scale.min) + self.scale.min,
+            Normalization::MeanCentering => value + self.mean,
+            Normalization::ZScore => value*self.spread() + self.mean
+        }
+    }
+
+    pub fn normalize_ratings<I: Clone + Eq + Hash>(&self, ratings: &HashMap<I, f64>) -> HashMap<I, f64> {
+        ratings.iter().map(|(item, rating)| (item.clone(), self.normalize(*rating))).collect()
+    }
+
+    /// Users rating everything the same have no deviation, their z-scores are just centered.
+    fn spread(&self) -> f64 {
+        if self.std_dev > 0.0 { self.std_dev } else { 1.0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scales() {
+        let movielens = RatingScale::create(0.5, 5.0, Some(0.5));
+        assert!(movielens.contains(3.5));
+        assert!(!movielens.contains(3.2));
+        assert!(!movielens.contains(0.0));
+        assert_eq!(movielens.clamp(5.7), 5.0);
+        assert_eq!(movielens.clamp(-1.0), 0.5);
+    }
+
+    #[test]
+    fn normalizations_are_reversible() {
+        let scale = RatingScale::create(1.0, 5.0, Some(1.0));
+        let ratings: HashMap<i32, f64> = vec![(1, 1.0), (2, 3.0), (3, 5.0)].into_iter().collect();
+
+        for method in &[Normalization::None, Normalization::MinMax, Normalization::MeanCentering, Normalization::ZScore] {
+            let normalizer = Normalizer::fit(*method, scale, &ratings);
+            assert!((normalizer.denormalize(normalizer.normalize(4.0)) - 4.0).abs() < 1e-9);
+        }
+
+        assert_eq!(Normalizer::fit(Normalization::MinMax, scale, &ratings).normalize(3.0), 0.5);
+        assert_eq!(Normalizer::fit(Normalization::MeanCentering, scale, &ratings).normalize(5.0), 2.0);
+    }
+}
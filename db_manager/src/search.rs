@@ -0,0 +1,181 @@
+use std::cmp::{min, Ordering};
+use std::collections::HashSet;
+
+/// Minimum score a candidate needs to be returned by a fuzzy search.
+pub const FUZZY_THRESHOLD: f64 = 0.3;
+
+/// Most candidates a fuzzy search reads from the database, the closest ones by trigrams.
+pub const FUZZY_CANDIDATES: i64 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchMode {
+    /// Case-insensitive equality.
+    Exact,
+    /// Case-insensitive prefix match.
+    Prefix,
+    /// Case-insensitive substring match.
+    Substring,
+    /// Edit distance and trigram similarity, tolerant to typos and missing words.
+    Fuzzy
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchResult<I> {
+    pub item: I,
+    pub score: f64
+}
+
+/// Builds the `ILIKE` pattern used to prefilter candidates in the database.
+/// Fuzzy searches are prefiltered by `pg_trgm` similarity instead, so they return `None`.
+pub fn like_pattern(query: &str, mode: MatchMode) -> Option<String> {
+    let escaped = escape_like(query);
+    match mode {
+        MatchMode::Exact => Some(escaped),
+        MatchMode::Prefix => Some(format!("{}%", escaped)),
+        MatchMode::Substring => Some(format!("%{}%", escaped)),
+        MatchMode::Fuzzy => None
+    }
+}
+
+pub fn escape_like(query: &str) -> String {
+    let mut escaped = String::with_capacity(query.len());
+    for c in query.chars() {
+        if c == '\\' || c == '%' || c == '_' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Scores how well `candidate` matches `query` in [0, 1], or `None` if it doesn't match at all.
+pub fn score(query: &str, candidate: &str, mode: MatchMode) -> Option<f64> {
+    let query = query.to_lowercase();
+    let candidate = candidate.to_lowercase();
+
+    let query_len = query.chars().count() as f64;
+    let candidate_len = candidate.chars().count().max(1) as f64;
+
+    match mode {
+        MatchMode::Exact => {
+            if query == candidate { Some(1.0) } else { None }
+        }
+        MatchMode::Prefix => {
+            if candidate.starts_with(&query) { Some(query_len/candidate_len) } else { None }
+        }
+        MatchMode::Substring => {
+            if candidate.contains(&query) { Some(query_len/candidate_len) } else { None }
+        }
+        MatchMode::Fuzzy => {
+            let contained = if candidate.contains(&query) { query_len/candidate_len } else { 0.0 };
+            let best = contained
+                .max(trigram_similarity(&query, &candidate))
+                .max(levenshtein_similarity(&query, &candidate));
+
+            if best >= FUZZY_THRESHOLD { Some(best) } else { None }
+        }
+    }
+}
+
+/// Scores every candidate and returns the best `limit` ones, highest score first.
+pub fn rank<T>(query: &str, mode: MatchMode, candidates: Vec<(T, String)>, limit: usize) -> Vec<(T, f64)> {
+    let mut ranked = Vec::new();
+    for (candidate, name) in candidates {
+        if let Some(score) = score(query, &name, mode) {
+            ranked.push((candidate, score));
+        }
+    }
+
+    ranked.sort_by(|first, second| second.1.partial_cmp(&first.1).unwrap_or(Ordering::Equal));
+    ranked.truncate(limit);
+    ranked
+}
+
+pub fn levenshtein_distance(first: &str, second: &str) -> usize {
+    let first: Vec<char> = first.chars().collect();
+    let second: Vec<char> = second.chars().collect();
+
+    let mut previous: Vec<usize> = (0..=second.len()).collect();
+    let mut current = vec![0; second.len() + 1];
+
+    for i in 1..=first.len() {
+        current[0] = i;
+        for j in 1..=second.len() {
+            let substitution = if first[i-1] == second[j-1] { 0 } else { 1 };
+            current[j] = min(min(previous[j] + 1, current[j-1] + 1), previous[j-1] + substitution);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[second.len()]
+}
+
+pub fn levenshtein_similarity(first: &str, second: &str) -> f64 {
+    let longest = first.chars().count().max(second.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein_distance(first, second) as f64/longest as f64
+}
+
+/// Jaccard index between the padded character trigrams of both strings, like `pg_trgm` does.
+pub fn trigram_similarity(first: &str, second: &str) -> f64 {
+    let first = trigrams(first);
+    let second = trigrams(second);
+
+    if first.is_empty() && second.is_empty() {
+        return 1.0;
+    }
+
+    let intersection = first.intersection(&second).count();
+    let union = first.len() + second.len() - intersection;
+    intersection as f64/union as f64
+}
+
+fn trigrams(text: &str) -> HashSet<(char, char, char)> {
+    let mut result = HashSet::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        let padded: Vec<char> = "  ".chars().chain(word.chars()).chain(" ".chars()).collect();
+        for window in padded.windows(3) {
+            result.insert((window[0], window[1], window[2]));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein() {
+        assert_eq!(levenshtein_distance("kitten", "sitting"), 3);
+        assert_eq!(levenshtein_distance("", "abc"), 3);
+        assert_eq!(levenshtein_distance("Avatar", "Avatar"), 0);
+    }
+
+    #[test]
+    fn like_patterns_are_escaped() {
+        assert_eq!(like_pattern("100%_a\\b", MatchMode::Substring).unwrap(), "%100\\%\\_a\\\\b%");
+        assert_eq!(like_pattern("Jaws", MatchMode::Prefix).unwrap(), "Jaws%");
+        assert_eq!(like_pattern("Jaws", MatchMode::Fuzzy), None);
+    }
+
+    #[test]
+    fn ranking() {
+        let candidates = vec![
+            (1, String::from("Spider-Man (2002)")),
+            (2, String::from("Spider-Man 2 (2004)")),
+            (3, String::from("Iron Will (1994)")),
+        ];
+
+        let ranked = rank("spider-man", MatchMode::Substring, candidates.clone(), 10);
+        assert_eq!(ranked.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![1, 2]);
+
+        let ranked = rank("Spiderman", MatchMode::Fuzzy, candidates.clone(), 10);
+        assert_eq!(ranked[0].0, 1);
+        assert!(ranked.iter().all(|(id, _)| *id != 3));
+
+        assert!(rank("Spider-Man", MatchMode::Exact, candidates, 10).is_empty());
+    }
+}
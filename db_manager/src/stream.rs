@@ -0,0 +1,66 @@
+use std::marker::PhantomData;
+
+use crate::{DBManager, User, Item};
+
+/// A rating with its row id, the key `DBManager::get_ratings_after` pages by.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatingRow<UID, IID> {
+    pub key: i64,
+    pub user: UID,
+    pub item: IID,
+    pub rating: f64
+}
+
+/// Every explicit rating as `(user, item, rating)`, read in batches by keyset pagination so only
+/// one batch is in memory. Ratings added while iterating after the last key read are included.
+pub struct RatingsIter<'a, M, U, I>
+where
+    U: User<I>,
+    I: Item
+{
+    manager: &'a M,
+    batch_size: i64,
+    after: i64,
+    batch: std::vec::IntoIter<RatingRow<U::ID, I::ID>>,
+    finished: bool,
+    phantom: PhantomData<fn() -> (U, I)>
+}
+
+impl<'a, M, U, I> RatingsIter<'a, M, U, I>
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item
+{
+    pub fn create(manager: &'a M, batch_size: i64) -> RatingsIter<'a, M, U, I> {
+        RatingsIter{manager, batch_size: batch_size.max(1), after: 0, batch: Vec::new().into_iter(), finished: false, phantom: PhantomData}
+    }
+}
+
+impl<'a, M, U, I> Iterator for RatingsIter<'a, M, U, I>
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item
+{
+    type Item = (U::ID, I::ID, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(row) = self.batch.next() {
+                return Some((row.user, row.item, row.rating));
+            }
+            if self.finished {
+                return None;
+            }
+
+            let batch = self.manager.get_ratings_after(self.after, self.batch_size);
+            // A short batch is the last one, no need to ask for an empty one
+            self.finished = (batch.len() as i64) < self.batch_size;
+            if let Some(last) = batch.last() {
+                self.after = last.key;
+            }
+            self.batch = batch.into_iter();
+        }
+    }
+}
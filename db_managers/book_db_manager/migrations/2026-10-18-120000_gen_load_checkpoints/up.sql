@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE load_checkpoints (
+    stage VARCHAR PRIMARY KEY,
+    chunks_done INTEGER NOT NULL
+)
\ No newline at end of file
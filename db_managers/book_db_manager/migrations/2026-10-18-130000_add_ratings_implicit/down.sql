@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE ratings DROP COLUMN implicit;
\ No newline at end of file
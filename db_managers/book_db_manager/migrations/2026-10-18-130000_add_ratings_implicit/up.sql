@@ -0,0 +1,5 @@
+-- Your SQL goes here
+ALTER TABLE ratings ADD COLUMN implicit BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Book-Crossing stores implicit interactions as a rating of 0
+UPDATE ratings SET implicit = TRUE WHERE rating = 0;
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX users_country_idx;
+ALTER TABLE users DROP COLUMN country;
+ALTER TABLE users DROP COLUMN state;
+ALTER TABLE users DROP COLUMN city;
+ALTER TABLE users RENAME COLUMN location TO city;
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+ALTER TABLE users RENAME COLUMN city TO location;
+ALTER TABLE users ADD COLUMN city VARCHAR;
+ALTER TABLE users ADD COLUMN state VARCHAR;
+ALTER TABLE users ADD COLUMN country VARCHAR;
+
+-- The rows already loaded are split with `cargo run --bin split_locations`, which normalizes
+-- the countries as dataset_importer's `import` does
+
+CREATE INDEX users_country_idx ON users (country);
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- Users created by the manager take their id from the sequence, the importer moves it past the loaded ids
+CREATE SEQUENCE users_id_seq OWNED BY users.id;
+SELECT setval('users_id_seq', COALESCE(MAX(id), 0) + 1, false) FROM users;
+ALTER TABLE users ALTER COLUMN id SET DEFAULT nextval('users_id_seq');
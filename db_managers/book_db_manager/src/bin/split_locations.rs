@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use diesel::prelude::*;
+use diesel::pg::PgConnection;
+
+use book_db_manager::schema::users;
+use dataset_importer::mapping::DatasetMapping;
+use db_manager::location::parse_location;
+
+/// Fills the city, state and country of the users loaded before the location was split,
+/// normalizing the countries the same way dataset_importer's `import` does.
+fn main() {
+    let mapping = DatasetMapping::from_file(&Path::new(env!("CARGO_MANIFEST_DIR")).join("import.toml"));
+    let connector = PgConnection::establish(&mapping.database_url).expect("Failed connection to database. Maybe the URL?");
+
+    let locations = users::table
+        .select(users::location)
+        .distinct()
+        .load::<String>(&connector)
+        .expect("Failed query of the users locations");
+
+    let updated = connector.transaction::<_, diesel::result::Error, _>(|| {
+        let mut updated = 0;
+        for location in &locations {
+            let parts = parse_location(location);
+            updated += diesel::update(users::table.filter(users::location.eq(location)))
+                .set((users::city.eq(&parts.city), users::state.eq(&parts.state), users::country.eq(&parts.country)))
+                .execute(&connector)?;
+        }
+        Ok(updated)
+    }).expect("Failed to split the users locations");
+
+    println!("Split the location of {} users", updated);
+}
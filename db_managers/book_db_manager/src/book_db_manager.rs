@@ -1,30 +1,55 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 
 use diesel::prelude::*;
-use diesel::pg::PgConnection;
+use diesel::dsl::sql;
+use diesel::result::{DatabaseErrorKind, Error::DatabaseError};
+use diesel::sql_types::{BigInt, Bool, Float, Text};
 
-use db_manager::DBManager;
+use db_manager::{Connect, DBManager, MatchMode, RatingKind, RatingRow, RatingScale, SearchResult, UserQuery, WriteError, search};
+use db_manager::pool::{PgPool, PgPooledConnection, connect_pool};
+use db_manager::location::{normalize_country, parse_location};
 
 use crate::schema::{users, books, ratings};
-use crate::{book_user::{BookUser, QueryableUser}, book_item::{BookItem, QueryableItem}, book_rating::{QueryableRating}};
+use crate::{book_user::{BookUser, QueryableUser, NewUser}, book_item::{BookItem, QueryableItem, NewBook}, book_rating::{QueryableRating, NewRating}};
 
+/// Book-Crossing stores implicit interactions as ratings of 0, every method returning ratings
+/// leaves them out except `get_all_ratings_of` and `get_user_ratings_of`.
 pub struct BookDBManager {
-    connector:PgConnection
+    pool: PgPool
 }
 
-impl DBManager<BookUser, BookItem> for BookDBManager {
+impl BookDBManager {
+    /// Shares the connections of another manager of the same database.
+    pub fn from_pool(pool: PgPool) -> BookDBManager {
+        BookDBManager{pool}
+    }
+
+    fn connection(&self) -> PgPooledConnection {
+        self.pool.get().expect("Failed to get a connection from the pool")
+    }
+}
+
+impl Connect for BookDBManager {
     fn connect_to(url: &str) -> Self {
-        let connector = PgConnection::establish(url).expect("Failed connection to database. Maybe the URL?");
-        BookDBManager{connector: connector}
+        BookDBManager{pool: connect_pool(url)}
+    }
+}
+
+impl DBManager<BookUser, BookItem> for BookDBManager {
+    fn rating_scale(&self) -> RatingScale {
+        // Ratings of 0 are implicit interactions, out of the scale
+        RatingScale::create(1.0, 10.0, Some(1.0))
     }
 
     fn get_user_by_name(&self, name: &str) -> Vec<BookUser> {
-        vec![]
+        self.get_users_by_query(&UserQuery{name: Some(String::from(name)), ..Default::default()})
     }
 
     fn get_user_by_id(&self, uid: i32) -> Vec<BookUser> {
+        let connector = self.connection();
         let query_result = users::table.filter(users::id.eq(uid))
-            .load::<QueryableUser>(&self.connector)
+            .load::<QueryableUser>(&connector)
             .expect("Failed query of user with the uid specified");
 
         if query_result.is_empty() {
@@ -34,7 +59,8 @@ impl DBManager<BookUser, BookItem> for BookDBManager {
         let selected_user = &query_result[0];
 
         let query_result = ratings::table.filter(ratings::user_id.eq(selected_user.id))
-            .load::<QueryableRating>(&self.connector)
+            .filter(ratings::implicit.eq(false))
+            .load::<QueryableRating>(&connector)
             .expect(&format!("Failed query of ratings of the user {}", selected_user.id));
 
         let mut user_ratings = HashMap::new();
@@ -42,12 +68,67 @@ impl DBManager<BookUser, BookItem> for BookDBManager {
             user_ratings.insert(rating.book_id.clone(), rating.rating);
         }
 
-        vec![BookUser::create(selected_user.id, user_ratings, selected_user.city.clone(), selected_user.age)]
+        vec![BookUser::create(selected_user.id, user_ratings, selected_user.location.clone(), selected_user.city.clone(), selected_user.state.clone(), selected_user.country.clone(), selected_user.age)]
+    }
+
+    fn get_users_by_query(&self, query: &UserQuery) -> Vec<BookUser> {
+        if query.name.is_some() {
+            return Vec::new();
+        }
+
+        let mut selection = users::table.order(users::id).into_boxed();
+        if let Some(city) = &query.city {
+            selection = selection.filter(users::city.ilike(format!("%{}%", search::escape_like(city.trim()))));
+        }
+        if let Some(state) = &query.state {
+            selection = selection.filter(users::state.ilike(format!("%{}%", search::escape_like(state.trim()))));
+        }
+        if let Some(country) = &query.country {
+            match normalize_country(country) {
+                Some(country) => selection = selection.filter(users::country.eq(country)),
+                None => return Vec::new()
+            }
+        }
+        if let Some(min_age) = query.min_age {
+            selection = selection.filter(users::age.ge(min_age));
+        }
+        if let Some(max_age) = query.max_age {
+            selection = selection.filter(users::age.le(max_age));
+        }
+        if let Some(min_ratings) = query.min_ratings {
+            selection = selection.filter(sql::<Bool>("(SELECT COUNT(*) FROM ratings WHERE ratings.user_id = users.id AND NOT ratings.implicit) >= ").bind::<BigInt, _>(min_ratings));
+        }
+        if let Some(offset) = query.offset {
+            selection = selection.offset(offset);
+        }
+        if let Some(limit) = query.limit {
+            selection = selection.limit(limit);
+        }
+
+        let connector = self.connection();
+        let query_result = selection
+            .load::<QueryableUser>(&connector)
+            .expect("Failed query of users with the given criteria");
+
+        let ratings_by_user = QueryableRating::belonging_to(&query_result)
+            .filter(ratings::implicit.eq(false))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed query of ratings of the selected users")
+            .grouped_by(&query_result);
+
+        let mut result = Vec::new();
+        for (selected_user, user_ratings) in query_result.into_iter().zip(ratings_by_user) {
+            let user_ratings = user_ratings.into_iter().map(|rating| (rating.book_id, rating.rating)).collect();
+            result.push(BookUser::create(selected_user.id, user_ratings, selected_user.location, selected_user.city, selected_user.state, selected_user.country, selected_user.age));
+        }
+
+        result
     }
 
     fn get_item_by_name(&self, name: &str) -> Vec<BookItem> {
+        let connector = self.connection();
         let query_result = books::table.filter(books::title.eq(name))
-            .load::<QueryableItem>(&self.connector)
+            .load::<QueryableItem>(&connector)
             .expect("Failed query of book with the given title");
 
         let mut result = Vec::new();
@@ -68,8 +149,9 @@ impl DBManager<BookUser, BookItem> for BookDBManager {
     }
 
     fn get_item_by_id(&self, uid: String) -> Vec<BookItem> {
+        let connector = self.connection();
         let query_result = books::table.filter(books::id.eq(uid))
-            .load::<QueryableItem>(&self.connector)
+            .load::<QueryableItem>(&connector)
             .expect("Failed query of book with the given uid");
 
         if query_result.is_empty() {
@@ -89,16 +171,70 @@ impl DBManager<BookUser, BookItem> for BookDBManager {
         ]
     }
 
+    fn get_users_by_ids(&self, uids: &[i32]) -> HashMap<i32, BookUser> {
+        let connector = self.connection();
+        let query_result = users::table.filter(users::id.eq_any(uids))
+            .load::<QueryableUser>(&connector)
+            .expect("Failed query of users with the given uids");
+
+        let ratings_by_user = QueryableRating::belonging_to(&query_result)
+            .filter(ratings::implicit.eq(false))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed query of ratings of the selected users")
+            .grouped_by(&query_result);
+
+        let mut result = HashMap::new();
+        for (selected_user, user_ratings) in query_result.into_iter().zip(ratings_by_user) {
+            let user_ratings = user_ratings.into_iter().map(|rating| (rating.book_id, rating.rating)).collect();
+            result.insert(selected_user.id, BookUser::create(selected_user.id, user_ratings, selected_user.location, selected_user.city, selected_user.state, selected_user.country, selected_user.age));
+        }
+
+        result
+    }
+
+    fn get_items_by_ids(&self, iids: &[String]) -> HashMap<String, BookItem> {
+        let connector = self.connection();
+        let query_result = books::table.filter(books::id.eq_any(iids))
+            .load::<QueryableItem>(&connector)
+            .expect("Failed query of books with the given uids");
+
+        query_result.into_iter().map(|book| (book.id.clone(), BookItem::create(book.id, book.title, book.author, book.pub_year, book.publisher))).collect()
+    }
+
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> Vec<SearchResult<BookItem>> {
+        let connector = self.connection();
+        let candidates = match search::like_pattern(query, mode) {
+            Some(pattern) => books::table.filter(books::title.ilike(pattern)).load::<QueryableItem>(&connector),
+            // Similar titles, or titles with a part similar to the query, by the trigram index
+            None => books::table
+                .filter(sql::<Bool>("title % ").bind::<Text, _>(query).sql(" OR ").bind::<Text, _>(query).sql(" <% title"))
+                .order(sql::<Float>("word_similarity(").bind::<Text, _>(query).sql(", title) DESC"))
+                .limit(search::FUZZY_CANDIDATES)
+                .load::<QueryableItem>(&connector)
+        }.expect("Failed search of books with the given query");
+
+        let candidates = candidates.into_iter().map(|book| {
+            let title = book.title.clone();
+            (BookItem::create(book.id, book.title, book.author, book.pub_year, book.publisher), title)
+        }).collect();
+
+        search::rank(query, mode, candidates, limit).into_iter()
+            .map(|(item, score)| SearchResult{item, score})
+            .collect()
+    }
+
     fn get_all_users(&self) -> Vec<BookUser> {
+        let connector = self.connection();
         let query_result = users::table
-            .load::<QueryableUser>(&self.connector)
+            .load::<QueryableUser>(&connector)
             .expect("Failed query of all users");
 
         let mut result = Vec::new();
 
         for selected_user in &query_result {
             let query_result = ratings::table.filter(ratings::user_id.eq(selected_user.id))
-                .load::<QueryableRating>(&self.connector)
+                .filter(ratings::implicit.eq(false))
+                .load::<QueryableRating>(&connector)
                 .expect(&format!("Failed query of ratings of the user {}", selected_user.id));
             
             let mut user_ratings = HashMap::new();
@@ -106,65 +242,257 @@ impl DBManager<BookUser, BookItem> for BookDBManager {
                 user_ratings.insert(rating.book_id.clone(), rating.rating);
             }
 
-            result.push(BookUser::create(selected_user.id, user_ratings, selected_user.city.clone(), selected_user.age));
+            result.push(BookUser::create(selected_user.id, user_ratings, selected_user.location.clone(), selected_user.city.clone(), selected_user.state.clone(), selected_user.country.clone(), selected_user.age));
         }
 
         result
     }
 
     fn get_all_ratings(&self) -> HashMap<i32, HashMap<String, f64>> {
-        let query_result = ratings::table
-            .load::<QueryableRating>(&self.connector)
-            .expect("Failed to fetch all ratings");
-
-        let mut result = HashMap::new();
-        for rating in &query_result {
-            if !result.contains_key(&rating.user_id) {
-                result.insert(rating.user_id, HashMap::new());
-            }
-            let user_ratings = result.get_mut(&rating.user_id).unwrap();
-            user_ratings.insert(rating.book_id.clone(), rating.rating);
-        }
-        result
+        self.get_all_ratings_of(RatingKind::Explicit)
     }
-    fn get_users_chunk(&self, offset: i64, limit: i64) -> Vec<i32> {
+    fn get_users_chunk(&self, after: Option<i32>, limit: i64) -> Vec<i32> {
+        let connector = self.connection();
         let user_chunk = users::table
             .select(users::id)
+            .filter(users::id.gt(after.unwrap_or(i32::MIN)))
+            .order(users::id)
             .limit(limit)
-            .offset(offset)
-            .load::<i32>(&self.connector)
+            .load::<i32>(&connector)
             .expect("Failed to fetch chunk of users");
         
         user_chunk
     }
+    fn get_items_chunk(&self, after: Option<String>, limit: i64) -> Vec<BookItem> {
+        let connector = self.connection();
+        let mut selection = books::table.into_boxed();
+        if let Some(after) = after {
+            selection = selection.filter(books::id.gt(after));
+        }
+
+        let query_result = selection
+            .order(books::id)
+            .limit(limit)
+            .load::<QueryableItem>(&connector)
+            .expect("Failed to fetch chunk of books");
+
+        query_result.into_iter().map(|book| BookItem::create(book.id, book.title, book.author, book.pub_year, book.publisher)).collect()
+    }
     fn get_user_ratings(&self, uid: i32) -> HashMap<String, f64> {
-        let user:QueryableUser = users::table.find(uid).get_result(&self.connector).unwrap();
-        let query_result = QueryableRating::belonging_to(&user).load::<QueryableRating>(&self.connector).unwrap();
+        self.get_user_ratings_of(uid, RatingKind::Explicit)
+    }
+    fn get_users_with_ratings_chunk(&self, after: Option<i32>, limit: i64) -> HashMap<i32, HashMap<String, f64>> {
+        let connector = self.connection();
+        let user_chunk = users::table
+            .filter(users::id.gt(after.unwrap_or(i32::MIN)))
+            .order(users::id)
+            .limit(limit)
+            .load::<QueryableUser>(&connector)
+            .expect("Failed to fetch chunk of users");
+
+        let query_result = QueryableRating::belonging_to(&user_chunk)
+            .filter(ratings::implicit.eq(false))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed to fetch chunk of ratings");
+
+        let mut users_with_ratings = HashMap::new();
+        for rating in query_result {
+            users_with_ratings.entry(rating.user_id).or_insert_with(HashMap::new).insert(rating.book_id, rating.rating);
+        }
+
+        users_with_ratings
+    }
+    fn get_item_ratings(&self, iid: String) -> HashMap<i32, f64> {
+        let connector = self.connection();
+        let query_result = ratings::table
+            .filter(ratings::book_id.eq(iid))
+            .filter(ratings::implicit.eq(false))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed query of ratings of the book");
+
+        query_result.into_iter().map(|rating| (rating.user_id, rating.rating)).collect()
+    }
+    fn get_items_with_ratings_chunk(&self, after: Option<String>, limit: i64) -> HashMap<String, HashMap<i32, f64>> {
+        let connector = self.connection();
+        let mut selection = books::table.select(books::id).into_boxed();
+        if let Some(after) = after {
+            selection = selection.filter(books::id.gt(after));
+        }
+
+        let item_chunk = selection
+            .order(books::id)
+            .limit(limit)
+            .load::<String>(&connector)
+            .expect("Failed to fetch chunk of books");
+
+        let query_result = ratings::table
+            .filter(ratings::book_id.eq_any(item_chunk))
+            .filter(ratings::implicit.eq(false))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed to fetch chunk of ratings");
+
+        let mut items_with_ratings = HashMap::new();
+        for rating in query_result {
+            items_with_ratings.entry(rating.book_id).or_insert_with(HashMap::new).insert(rating.user_id, rating.rating);
+        }
+
+        items_with_ratings
+    }
+    fn get_item_rating_counts(&self) -> HashMap<String, i64> {
+        let connector = self.connection();
+        let query_result = ratings::table
+            .filter(ratings::implicit.eq(false))
+            .group_by(ratings::book_id)
+            .select((ratings::book_id, sql::<BigInt>("COUNT(*)")))
+            .load::<(String, i64)>(&connector)
+            .expect("Failed to count the ratings of every book");
+
+        query_result.into_iter().collect()
+    }
+    fn count_users(&self) -> i64 {
+        let connector = self.connection();
+        users::table.count().get_result(&connector).expect("Failed to count the users")
+    }
+    fn count_items(&self) -> i64 {
+        let connector = self.connection();
+        books::table.count().get_result(&connector).expect("Failed to count the books")
+    }
+    fn count_ratings(&self) -> i64 {
+        let connector = self.connection();
+        ratings::table
+            .filter(ratings::implicit.eq(false))
+            .count()
+            .get_result(&connector)
+            .expect("Failed to count the ratings")
+    }
+    fn get_ratings_after(&self, after: i64, limit: i64) -> Vec<RatingRow<i32, String>> {
+        let after = match i32::try_from(after) {
+            Ok(after) => after,
+            // No id of the column is past it, or every id is
+            Err(_) if after > 0 => return Vec::new(),
+            Err(_) => i32::MIN
+        };
+
+        let connector = self.connection();
+        let query_result = ratings::table
+            .filter(ratings::id.gt(after))
+            .filter(ratings::implicit.eq(false))
+            .order(ratings::id)
+            .limit(limit)
+            .load::<QueryableRating>(&connector)
+            .expect("Failed to fetch the page of explicit ratings");
+
+        query_result.into_iter()
+            .map(|rating| RatingRow{key: rating.id as i64, user: rating.user_id, item: rating.book_id, rating: rating.rating})
+            .collect()
+    }
+
+    fn get_all_ratings_of(&self, kind: RatingKind) -> HashMap<i32, HashMap<String, f64>> {
+        let connector = self.connection();
+        let query_result = ratings::table
+            .filter(ratings::implicit.eq(kind == RatingKind::Implicit))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed to fetch all ratings");
+
+        let mut result = HashMap::new();
+        for rating in query_result {
+            result.entry(rating.user_id).or_insert_with(HashMap::new).insert(rating.book_id, rating.rating);
+        }
+        result
+    }
+
+    fn get_user_ratings_of(&self, uid: i32, kind: RatingKind) -> HashMap<String, f64> {
+        let connector = self.connection();
+        let query_result = ratings::table
+            .filter(ratings::user_id.eq(uid))
+            .filter(ratings::implicit.eq(kind == RatingKind::Implicit))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed query of the ratings of the user");
 
         let mut ratings_by_item = HashMap::new();
-        for rating in &query_result {
-            ratings_by_item.insert(rating.book_id.clone(), rating.rating);
+        for rating in query_result {
+            ratings_by_item.insert(rating.book_id, rating.rating);
         }
 
         ratings_by_item
     }
-    fn get_users_with_ratings_chunk(&self, offset: i64, limit: i64) -> HashMap<i32, HashMap<String, f64>> {
-        /*let mut users_with_ratings = HashMap::new();
 
-        for uid in users_id {
-            let user:QueryableUser = users::table.find(uid).get_result(&self.connector).unwrap();
-            let query_result = QueryableRating::belonging_to(&user).load::<QueryableRating>(&self.connector).unwrap();
-            let mut ratings_by_item = HashMap::new();
-            for rating in &query_result {
-                ratings_by_item.insert(rating.book_id.clone(), rating.rating);
-            }
+    /// Ratings stored are explicit, replacing an implicit interaction with the book if there was one.
+    fn add_rating(&self, uid: i32, iid: String, rating: f64) -> Result<(), WriteError> {
+        if !self.rating_scale().contains(rating) {
+            return Err(WriteError::OutOfScale(rating));
+        }
 
-            users_with_ratings.insert(*uid, ratings_by_item);
+        let connector = self.connection();
+        if !diesel::select(diesel::dsl::exists(users::table.find(uid))).get_result::<bool>(&connector)? {
+            return Err(WriteError::UnknownUser);
         }
-        
-        users_with_ratings*/
-        HashMap::new()
+        if !diesel::select(diesel::dsl::exists(books::table.find(&iid))).get_result::<bool>(&connector)? {
+            return Err(WriteError::UnknownItem);
+        }
+
+        diesel::insert_into(ratings::table)
+            .values(&NewRating{user_id: uid, book_id: iid, rating, implicit: false})
+            .on_conflict((ratings::user_id, ratings::book_id))
+            .do_update()
+            .set((ratings::rating.eq(rating), ratings::implicit.eq(false)))
+            .execute(&connector)?;
+
+        Ok(())
+    }
+
+    fn delete_rating(&self, uid: i32, iid: String) -> bool {
+        let connector = self.connection();
+        let rating = ratings::table
+            .filter(ratings::user_id.eq(uid))
+            .filter(ratings::book_id.eq(iid))
+            .filter(ratings::implicit.eq(false));
+        let deleted = diesel::delete(rating)
+            .execute(&connector)
+            .expect("Failed to delete the rating");
+
+        deleted > 0
+    }
+
+    /// The "Location" is split into its parts like the imported ones, and
+    /// "Age" has to be a number.
+    fn create_user(&self, _name: &str, data: &HashMap<String, String>) -> Result<BookUser, WriteError> {
+        let age = match data.get("Age") {
+            Some(age) => Some(age.trim().parse::<i32>().map_err(|_| WriteError::InvalidData(format!("the age {} isn't a number", age)))?),
+            None => None
+        };
+        let location = data.get("Location").cloned().unwrap_or_default();
+        let parts = parse_location(&location);
+
+        let connector = self.connection();
+        let user = diesel::insert_into(users::table)
+            .values(&NewUser{location, age, city: parts.city, state: parts.state, country: parts.country})
+            .get_result::<QueryableUser>(&connector)?;
+
+        Ok(BookUser::create(user.id, HashMap::new(), user.location, user.city, user.state, user.country, user.age))
     }
 
-    
-}
\ No newline at end of file
+    /// Books are identified by their ISBN, given in the "ISBN" key along with the keys of `data()`.
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> Result<BookItem, WriteError> {
+        let isbn = match data.get("ISBN") {
+            Some(isbn) if !isbn.trim().is_empty() => isbn.trim().to_string(),
+            _ => return Err(WriteError::InvalidData(String::from("books need an ISBN")))
+        };
+        let connector = self.connection();
+        if diesel::select(diesel::dsl::exists(books::table.find(&isbn))).get_result::<bool>(&connector)? {
+            return Err(WriteError::InvalidData(format!("the book {} already exists", isbn)));
+        }
+
+        let attribute = |key: &str| data.get(key).cloned().unwrap_or_default();
+        let book = diesel::insert_into(books::table)
+            .values(&NewBook{id: isbn.clone(), title: String::from(name), author: attribute("Author"), pub_year: attribute("Publication Year"), publisher: attribute("Publisher")})
+            .get_result::<QueryableItem>(&connector)
+            .map_err(|error| match error {
+                // Created by someone else since it was checked
+                DatabaseError(DatabaseErrorKind::UniqueViolation, _) => WriteError::InvalidData(format!("the book {} already exists", isbn)),
+                error => WriteError::from(error)
+            })?;
+
+        Ok(BookItem::create(book.id, book.title, book.author, book.pub_year, book.publisher))
+    }
+}
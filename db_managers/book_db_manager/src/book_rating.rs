@@ -10,7 +10,8 @@ pub struct QueryableRating {
     pub id: i32,
     pub user_id: i32,
     pub book_id: String,
-    pub rating: f64
+    pub rating: f64,
+    pub implicit: bool
 }
 
 #[derive(Insertable)]
@@ -18,5 +19,6 @@ pub struct QueryableRating {
 pub struct NewRating {
     pub user_id: i32,
     pub book_id: String,
-    pub rating: f64
+    pub rating: f64,
+    pub implicit: bool
 }
\ No newline at end of file
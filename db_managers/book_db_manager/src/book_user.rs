@@ -10,16 +10,21 @@ use crate::book_item::BookItem;
 #[table_name = "users"]
 pub struct QueryableUser {
     pub id: i32,
-    pub city: String,
-    pub age: Option<i32>
+    pub location: String,
+    pub age: Option<i32>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>
 }
 
 #[derive(Insertable)]
 #[table_name="users"]
 pub struct NewUser {
-    pub id: i32,
-    pub city: String,
-    pub age: Option<i32>
+    pub location: String,
+    pub age: Option<i32>,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>
 }
 
 #[derive(Debug,Clone)]
@@ -47,9 +52,16 @@ impl User<BookItem> for BookUser {
 }
 
 impl BookUser {
-    pub fn create(id:i32, ratings:HashMap<String, f64>, city:String, age:Option<i32>) -> BookUser {
+    pub fn create(id:i32, ratings:HashMap<String, f64>, location:String, city:Option<String>, state:Option<String>, country:Option<String>, age:Option<i32>) -> BookUser {
         let mut extra_data:HashMap<String,String> = HashMap::new();
-        extra_data.insert(String::from("City"), city);
+        extra_data.insert(String::from("Location"), location);
+
+        let parts = vec![("City", city), ("State", state), ("Country", country)];
+        for (key, value) in parts {
+            if let Some(value) = value {
+                extra_data.insert(String::from(key), value);
+            }
+        }
 
         if let Some(age) = age {
             extra_data.insert(String::from("Age"), age.to_string());
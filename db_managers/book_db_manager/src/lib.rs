@@ -11,7 +11,7 @@ pub mod book_db_manager;
 #[cfg(test)]
 mod tests {
     use super::book_db_manager::BookDBManager;
-    use db_manager::{DBManager};
+    use db_manager::{Connect, DBManager, MatchMode, RatingKind, User, UserQuery};
 
     #[test]
     fn query_user() {
@@ -25,6 +25,21 @@ mod tests {
         
         println!("{:?}\n", users);
 
+        let users = manager.get_users_by_query(&UserQuery{country: Some(String::from("United States")), min_age: Some(18), ..Default::default()});
+
+        println!("{:?}\n", users);
+
+        // Implicit interactions don't count, every user of the fixture has one explicit rating
+        let users = manager.get_users_by_query(&UserQuery{min_ratings: Some(1), ..Default::default()});
+        assert_eq!(users.len(), 4);
+        assert!(manager.get_users_by_query(&UserQuery{min_ratings: Some(2), ..Default::default()}).is_empty());
+
+        // Cities and states match by substring
+        let users = manager.get_users_by_query(&UserQuery{city: Some(String::from("TOCK")), ..Default::default()});
+        assert_eq!(users.iter().map(|user| user.id()).collect::<Vec<i32>>(), vec![2]);
+        let users = manager.get_users_by_query(&UserQuery{state: Some(String::from("york")), ..Default::default()});
+        assert_eq!(users.iter().map(|user| user.id()).collect::<Vec<i32>>(), vec![1]);
+
         //Dont do this, it takes a lot of time.. REFACTORING
         //let users = manager.get_all_users();
         
@@ -34,6 +49,10 @@ mod tests {
         
         println!("{:?}\n", books);
 
+        let books = manager.search_items("clara", MatchMode::Substring, 5);
+
+        println!("{:?}\n", books);
+
         let books = manager.get_item_by_id(String::from("0002005018"));
         
         println!("{:?}\n", books);
@@ -42,8 +61,51 @@ mod tests {
 
         println!("{:?}\n", ratings[&2]);
 
+        let signals = manager.get_all_signals();
+
+        println!("{:?}\n{:?}\n", signals.explicit[&2], signals.implicit[&2]);
+
         let users = manager.get_user_by_id(2);
 
         println!("{:?}\n", users);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn streamed_ratings() {
+        let manager = BookDBManager::connect_to("postgres://ademir:@localhost/books");
+        let all_ratings = manager.get_all_ratings();
+
+        let mut streamed = 0;
+        for (user, book, rating) in manager.ratings_iter(3) {
+            assert_eq!(all_ratings[&user][&book], rating);
+            streamed += 1;
+        }
+
+        assert_eq!(streamed, all_ratings.values().map(|ratings| ratings.len()).sum::<usize>());
+
+        // Keys out of the range of the ids aren't truncated
+        assert!(manager.get_ratings_after(i64::from(i32::MAX) + 1, 10).is_empty());
+        assert_eq!(manager.get_ratings_after(i64::from(i32::MIN) - 1, 10).len(), streamed);
+    }
+
+    #[test]
+    fn batch_lookups() {
+        let manager = BookDBManager::connect_to("postgres://ademir:@localhost/books");
+
+        let users = manager.get_users_by_ids(&[1, 2, -1]);
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[&2].ratings, manager.get_user_by_id(2)[0].ratings);
+
+        let books = manager.get_items_by_ids(&[String::from("0002005018"), String::from("missing")]);
+        println!("{:?}\n", books);
+        assert_eq!(books.len(), 1);
+    }
+
+    #[test]
+    fn implicit_ratings_are_not_deleted() {
+        let manager = BookDBManager::connect_to("postgres://ademir:@localhost/books");
+
+        assert!(!manager.delete_rating(2, String::from("0195153448")));
+        assert!(manager.get_user_ratings_of(2, RatingKind::Implicit).contains_key("0195153448"));
+    }
+}
@@ -8,20 +8,32 @@ table! {
     }
 }
 
+table! {
+    load_checkpoints (stage) {
+        stage -> Varchar,
+        fingerprint -> Varchar,
+        last_key -> Varchar,
+    }
+}
+
 table! {
     ratings (id) {
         id -> Int4,
         user_id -> Int4,
         book_id -> Varchar,
         rating -> Float8,
+        implicit -> Bool,
     }
 }
 
 table! {
     users (id) {
         id -> Int4,
-        city -> Varchar,
+        location -> Varchar,
         age -> Nullable<Int4>,
+        city -> Nullable<Varchar>,
+        state -> Nullable<Varchar>,
+        country -> Nullable<Varchar>,
     }
 }
 
@@ -30,6 +42,7 @@ joinable!(ratings -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
     books,
+    load_checkpoints,
     ratings,
     users,
 );
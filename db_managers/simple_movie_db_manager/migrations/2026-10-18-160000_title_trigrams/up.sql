@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Fuzzy searches prefilter titles with the trigram operators, served by this index
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX movies_title_trgm_idx ON movies USING gin (title gin_trgm_ops);
@@ -1,4 +1,5 @@
 use std::vec::Vec;
+use std::env;
 
 use simple_movie_db_manager::schema::{users, movies, ratings};
 use diesel::prelude::*;
@@ -34,6 +35,20 @@ fn create_rating(conn: &PgConnection, user_id:i32, movie_id:i32, rating:f64) ->
         .expect("Failed to insert new_rating")
 }
 
+fn reset(connector: &PgConnection) {
+    diesel::sql_query("TRUNCATE ratings, movies, users RESTART IDENTITY")
+        .execute(connector)
+        .expect("Failed to reset the tables");
+}
+
+/// The whole dataset is loaded in one transaction, so any user means it's loaded.
+fn is_loaded(connector: &PgConnection) -> bool {
+    let users = users::table.count()
+        .get_result::<i64>(connector)
+        .expect("Failed query of the loaded users");
+
+    users > 0
+}
 
 fn main() {
     let mut content = csv::ReaderBuilder::new()
@@ -55,28 +70,42 @@ fn main() {
 
     let connector = PgConnection::establish("postgres://ademir:@localhost/simple_movies").expect("Failed connection to database. Maybe the URL?");
 
-    let mut db_users = Vec::new();
+    if env::args().any(|arg| arg == "--reset") {
+        reset(&connector);
+    }
 
-    for col in 1..matrix[0].len() {
-        db_users.push(create_user(&connector, &matrix[0][col]));
+    if is_loaded(&connector) {
+        println!("The dataset is already loaded, use --reset to load it again");
+        return;
     }
 
-    let mut db_movies = Vec::new();
+    // The ids are generated by the database, so there is nothing to upsert on. Loading everything
+    // in one transaction keeps an interrupted load from leaving a half loaded database behind.
+    connector.transaction::<_, diesel::result::Error, _>(|| {
+        let mut db_users = Vec::new();
 
-    for row in 1..matrix.len() {
-        db_movies.push(create_movie(&connector, &matrix[row][0]));
-    }
+        for col in 1..matrix[0].len() {
+            db_users.push(create_user(&connector, &matrix[0][col]));
+        }
+
+        let mut db_movies = Vec::new();
 
+        for row in 1..matrix.len() {
+            db_movies.push(create_movie(&connector, &matrix[row][0]));
+        }
 
-    for i in 0..db_users.len() {
-        let current_user = &db_users[i];
 
-        for j in 0..db_movies.len() {
-            let current_movie = &db_movies[j];
-            if matrix[j+1][i+1] != "" {
-                create_rating(&connector, current_user.id, current_movie.id, matrix[j+1][i+1].parse().expect("Failed to parse"));  
+        for i in 0..db_users.len() {
+            let current_user = &db_users[i];
+
+            for j in 0..db_movies.len() {
+                let current_movie = &db_movies[j];
+                if matrix[j+1][i+1] != "" {
+                    create_rating(&connector, current_user.id, current_movie.id, matrix[j+1][i+1].parse().expect("Failed to parse"));  
+                }
             }
         }
-    }
 
+        Ok(())
+    }).expect("Failed to load the dataset");
 }
\ No newline at end of file
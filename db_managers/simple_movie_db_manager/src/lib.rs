@@ -11,7 +11,8 @@ pub mod movie_db_manager;
 #[cfg(test)]
 mod tests {
     use super::movie_db_manager::MovieDBManager;
-    use db_manager::{DBManager};
+    use super::movie_user::MovieUser;
+    use db_manager::{Connect, DBManager, MatchMode, UserQuery, WriteError};
 
     #[test]
     fn query_user() {
@@ -25,6 +26,10 @@ mod tests {
         
         println!("{:?}\n", users);
 
+        let users = manager.get_users_by_query(&UserQuery{min_ratings: Some(20), ..Default::default()});
+
+        println!("{:?}\n", users);
+
         let users = manager.get_all_users();
         
         println!("{:?}\n", users);
@@ -33,6 +38,10 @@ mod tests {
         
         println!("{:?}\n", movies);
 
+        let movies = manager.search_items("avatar", MatchMode::Substring, 5);
+
+        println!("{:?}\n", movies);
+
         let movies = manager.get_item_by_id(2);
         
         println!("{:?}\n", movies);
@@ -47,4 +56,79 @@ mod tests {
         
         println!("{:?}\n", users);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rating_writes() {
+        let manager = MovieDBManager::connect_to("postgres://ademir:@localhost/simple_movies");
+        let previous = manager.get_user_ratings(1)[&2];
+
+        assert_eq!(manager.add_rating(1, 2, 6.0), Err(WriteError::OutOfScale(6.0)));
+        assert_eq!(manager.add_rating(-1, 2, 3.0), Err(WriteError::UnknownUser));
+
+        manager.add_rating(1, 2, 1.0).unwrap();
+        assert_eq!(manager.get_user_ratings(1)[&2], 1.0);
+
+        assert!(manager.delete_rating(1, 2));
+        assert!(!manager.delete_rating(1, 2));
+
+        manager.add_rating(1, 2, previous).unwrap();
+        assert_eq!(manager.get_user_ratings(1)[&2], previous);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let manager = MovieDBManager::connect_to("postgres://ademir:@localhost/simple_movies");
+
+        let users: Vec<Vec<MovieUser>> = std::thread::scope(|scope| {
+            let manager = &manager;
+            let handles: Vec<_> = (1..=4).map(|uid| scope.spawn(move || manager.get_user_by_id(uid))).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        println!("{:?}\n", users);
+        assert!(users.iter().all(|user| user.len() == 1));
+    }
+
+    #[test]
+    fn async_matches_sync() {
+        use db_manager::{AsyncAdapter, AsyncDBManager};
+        use futures::StreamExt;
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let manager = MovieDBManager::connect_to("postgres://ademir:@localhost/simple_movies");
+        let expected = manager.get_all_ratings();
+
+        let manager = AsyncAdapter::create(manager);
+        let (users, ratings): (Vec<MovieUser>, Vec<_>) = runtime.block_on(async {
+            (manager.get_user_by_name("Chris").await, manager.ratings_stream(4).collect().await)
+        });
+
+        println!("{:?}\n", users);
+        assert_eq!(ratings.len(), expected.len());
+        assert!(ratings.iter().all(|(user, user_ratings)| expected[user] == *user_ratings));
+    }
+
+    #[test]
+    fn cached_reads() {
+        use db_manager::{CachingManager, CacheConfig};
+
+        let manager = CachingManager::create(MovieDBManager::connect_to("postgres://ademir:@localhost/simple_movies"), CacheConfig::default());
+        let chris: Vec<MovieUser> = manager.get_user_by_name("Chris");
+        assert_eq!(manager.get_user_by_name("Chris").len(), chris.len());
+
+        let previous = manager.get_user_ratings(3)[&4];
+        manager.add_rating(3, 4, 1.0).unwrap();
+        assert_eq!(manager.get_user_ratings(3)[&4], 1.0);
+        manager.add_rating(3, 4, previous).unwrap();
+        assert_eq!(manager.get_user_ratings(3)[&4], previous);
+
+        let users = manager.get_users_by_ids(&[1, 2, -1]);
+        assert_eq!(manager.get_users_by_ids(&[1, 2, -1]).len(), users.len());
+
+        let stats = manager.stats();
+        println!("{:?}\n", stats);
+        assert_eq!(stats["get_user_by_name"].hits, 1);
+        assert_eq!(stats["get_user_ratings"].misses, 3);
+        assert_eq!(stats["get_user_by_id"].hits, 3);
+    }
+}
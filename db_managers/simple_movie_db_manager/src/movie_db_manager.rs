@@ -1,50 +1,51 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 
 use diesel::prelude::*;
-use diesel::pg::PgConnection;
+use diesel::dsl::sql;
+use diesel::sql_types::{BigInt, Bool, Float, Text};
 
-use db_manager::DBManager;
+use db_manager::{Connect, DBManager, MatchMode, RatingKind, RatingRow, RatingScale, SearchResult, UserQuery, WriteError, search};
+use db_manager::pool::{PgPool, PgPooledConnection, connect_pool};
 
 use crate::schema::{users, movies, ratings};
-use crate::{movie_user::{MovieUser, QueryableUser}, movie_item::{MovieItem, QueryableItem}, movie_rating::{QueryableRating}};
+use crate::{movie_user::{MovieUser, QueryableUser, NewUser}, movie_item::{MovieItem, QueryableItem, NewMovie}, movie_rating::{QueryableRating, NewRating}};
 
 pub struct MovieDBManager {
-    connector:PgConnection
+    pool: PgPool
 }
 
-impl DBManager<MovieUser, MovieItem> for MovieDBManager {
-    fn connect_to(url: &str) -> Self {
-        let connector = PgConnection::establish(url).expect("Failed connection to database. Maybe the URL?");
-        MovieDBManager{connector: connector}
+impl MovieDBManager {
+    /// Shares the connections of another manager of the same database.
+    pub fn from_pool(pool: PgPool) -> MovieDBManager {
+        MovieDBManager{pool}
     }
 
-    fn get_user_by_name(&self, name: &str) -> Vec<MovieUser> {
-        let query_result = users::table.filter(users::username.eq(name))
-            .load::<QueryableUser>(&self.connector)
-            .expect("Failed query of users with the username specified");
-
-        let mut result = Vec::new();
+    fn connection(&self) -> PgPooledConnection {
+        self.pool.get().expect("Failed to get a connection from the pool")
+    }
+}
 
-        for selected_user in &query_result {
-            let query_result = ratings::table.filter(ratings::user_id.eq(selected_user.id))
-                .load::<QueryableRating>(&self.connector)
-                .expect(&format!("Failed query of ratings of the user {}", selected_user.username));
-            
-            let mut user_ratings = HashMap::new();
-            for rating in &query_result {
-                user_ratings.insert(rating.movie_id, rating.rating);
-            }
+impl Connect for MovieDBManager {
+    fn connect_to(url: &str) -> Self {
+        MovieDBManager{pool: connect_pool(url)}
+    }
+}
 
-            result.push(MovieUser{id: selected_user.id, name: selected_user.username.clone(), ratings: user_ratings});
-        }
+impl DBManager<MovieUser, MovieItem> for MovieDBManager {
+    fn rating_scale(&self) -> RatingScale {
+        RatingScale::create(1.0, 5.0, Some(1.0))
+    }
 
-        result
+    fn get_user_by_name(&self, name: &str) -> Vec<MovieUser> {
+        self.get_users_by_query(&UserQuery{name: Some(String::from(name)), ..Default::default()})
     }
 
     fn get_user_by_id(&self, uid: i32) -> Vec<MovieUser> {
+        let connector = self.connection();
         let query_result = users::table.filter(users::id.eq(uid as i32))
             .limit(1)
-            .load::<QueryableUser>(&self.connector)
+            .load::<QueryableUser>(&connector)
             .expect("Failed query of user with the uid specified");
 
         if query_result.is_empty() {
@@ -54,7 +55,7 @@ impl DBManager<MovieUser, MovieItem> for MovieDBManager {
         let selected_user = &query_result[0];
 
         let query_result = ratings::table.filter(ratings::user_id.eq(selected_user.id))
-            .load::<QueryableRating>(&self.connector)
+            .load::<QueryableRating>(&connector)
             .expect(&format!("Failed query of ratings of the user {}", selected_user.username));
         
         let mut user_ratings = HashMap::new();
@@ -65,9 +66,48 @@ impl DBManager<MovieUser, MovieItem> for MovieDBManager {
         vec![MovieUser{id: selected_user.id, name: selected_user.username.clone(), ratings: user_ratings}]
     }
 
+    fn get_users_by_query(&self, query: &UserQuery) -> Vec<MovieUser> {
+        if query.city.is_some() || query.state.is_some() || query.country.is_some() || query.min_age.is_some() || query.max_age.is_some() {
+            return Vec::new();
+        }
+
+        let mut selection = users::table.order(users::id).into_boxed();
+        if let Some(name) = &query.name {
+            selection = selection.filter(users::username.eq(name.clone()));
+        }
+        if let Some(min_ratings) = query.min_ratings {
+            selection = selection.filter(sql::<Bool>("(SELECT COUNT(*) FROM ratings WHERE ratings.user_id = users.id) >= ").bind::<BigInt, _>(min_ratings));
+        }
+        if let Some(offset) = query.offset {
+            selection = selection.offset(offset);
+        }
+        if let Some(limit) = query.limit {
+            selection = selection.limit(limit);
+        }
+
+        let connector = self.connection();
+        let query_result = selection
+            .load::<QueryableUser>(&connector)
+            .expect("Failed query of users with the given criteria");
+
+        let ratings_by_user = QueryableRating::belonging_to(&query_result)
+            .load::<QueryableRating>(&connector)
+            .expect("Failed query of ratings of the selected users")
+            .grouped_by(&query_result);
+
+        let mut result = Vec::new();
+        for (selected_user, user_ratings) in query_result.into_iter().zip(ratings_by_user) {
+            let user_ratings = user_ratings.into_iter().map(|rating| (rating.movie_id, rating.rating)).collect();
+            result.push(MovieUser{id: selected_user.id, name: selected_user.username, ratings: user_ratings});
+        }
+
+        result
+    }
+
     fn get_item_by_name(&self, name: &str) -> Vec<MovieItem> {
+        let connector = self.connection();
         let query_result = movies::table.filter(movies::title.eq(name))
-            .load::<QueryableItem>(&self.connector)
+            .load::<QueryableItem>(&connector)
             .expect("Failed query of movie with the given title");
 
         let mut result = Vec::new();
@@ -80,8 +120,9 @@ impl DBManager<MovieUser, MovieItem> for MovieDBManager {
     }
 
     fn get_item_by_id(&self, uid: i32) -> Vec<MovieItem> {
+        let connector = self.connection();
         let query_result = movies::table.filter(movies::id.eq(uid as i32))
-            .load::<QueryableItem>(&self.connector)
+            .load::<QueryableItem>(&connector)
             .expect("Failed query of movie with the given uid");
 
         if query_result.is_empty() {
@@ -91,16 +132,68 @@ impl DBManager<MovieUser, MovieItem> for MovieDBManager {
         vec![MovieItem{id: query_result[0].id, name: query_result[0].title.clone()}]
     }
 
+    fn get_users_by_ids(&self, uids: &[i32]) -> HashMap<i32, MovieUser> {
+        let connector = self.connection();
+        let query_result = users::table.filter(users::id.eq_any(uids))
+            .load::<QueryableUser>(&connector)
+            .expect("Failed query of users with the given uids");
+
+        let ratings_by_user = QueryableRating::belonging_to(&query_result)
+            .load::<QueryableRating>(&connector)
+            .expect("Failed query of ratings of the selected users")
+            .grouped_by(&query_result);
+
+        let mut result = HashMap::new();
+        for (selected_user, user_ratings) in query_result.into_iter().zip(ratings_by_user) {
+            let user_ratings = user_ratings.into_iter().map(|rating| (rating.movie_id, rating.rating)).collect();
+            result.insert(selected_user.id, MovieUser{id: selected_user.id, name: selected_user.username, ratings: user_ratings});
+        }
+
+        result
+    }
+
+    fn get_items_by_ids(&self, iids: &[i32]) -> HashMap<i32, MovieItem> {
+        let connector = self.connection();
+        let query_result = movies::table.filter(movies::id.eq_any(iids))
+            .load::<QueryableItem>(&connector)
+            .expect("Failed query of movies with the given uids");
+
+        query_result.into_iter().map(|movie| (movie.id, MovieItem{id: movie.id, name: movie.title})).collect()
+    }
+
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> Vec<SearchResult<MovieItem>> {
+        let connector = self.connection();
+        let candidates = match search::like_pattern(query, mode) {
+            Some(pattern) => movies::table.filter(movies::title.ilike(pattern)).load::<QueryableItem>(&connector),
+            // Similar titles, or titles with a part similar to the query, by the trigram index
+            None => movies::table
+                .filter(sql::<Bool>("title % ").bind::<Text, _>(query).sql(" OR ").bind::<Text, _>(query).sql(" <% title"))
+                .order(sql::<Float>("word_similarity(").bind::<Text, _>(query).sql(", title) DESC"))
+                .limit(search::FUZZY_CANDIDATES)
+                .load::<QueryableItem>(&connector)
+        }.expect("Failed search of movies with the given query");
+
+        let candidates = candidates.into_iter().map(|movie| {
+            let title = movie.title.clone();
+            (MovieItem{id: movie.id, name: movie.title}, title)
+        }).collect();
+
+        search::rank(query, mode, candidates, limit).into_iter()
+            .map(|(item, score)| SearchResult{item, score})
+            .collect()
+    }
+
     fn get_all_users(&self) -> Vec<MovieUser> {
+        let connector = self.connection();
         let query_result = users::table
-            .load::<QueryableUser>(&self.connector)
+            .load::<QueryableUser>(&connector)
             .expect("Failed query of all users");
 
         let mut result = Vec::new();
 
         for selected_user in &query_result {
             let query_result = ratings::table.filter(ratings::user_id.eq(selected_user.id))
-                .load::<QueryableRating>(&self.connector)
+                .load::<QueryableRating>(&connector)
                 .expect(&format!("Failed query of ratings of the user {}", selected_user.username));
             
             let mut user_ratings = HashMap::new();
@@ -115,8 +208,9 @@ impl DBManager<MovieUser, MovieItem> for MovieDBManager {
     }
 
     fn get_all_ratings(&self) -> HashMap<i32, HashMap<i32, f64>> {
+        let connector = self.connection();
         let query_result = ratings::table
-            .load::<QueryableRating>(&self.connector)
+            .load::<QueryableRating>(&connector)
             .expect("Failed to fetch all ratings");
 
         let mut result = HashMap::new();
@@ -129,19 +223,33 @@ impl DBManager<MovieUser, MovieItem> for MovieDBManager {
         }
         result
     }
-    fn get_users_chunk(&self, offset: i64, limit: i64) -> Vec<i32> {
+    fn get_users_chunk(&self, after: Option<i32>, limit: i64) -> Vec<i32> {
+        let connector = self.connection();
         let user_chunk = users::table
             .select(users::id)
+            .filter(users::id.gt(after.unwrap_or(i32::MIN)))
+            .order(users::id)
             .limit(limit)
-            .offset(offset)
-            .load::<i32>(&self.connector)
+            .load::<i32>(&connector)
             .expect("Failed to fetch chunk of users");
         
         user_chunk
     }
+    fn get_items_chunk(&self, after: Option<i32>, limit: i64) -> Vec<MovieItem> {
+        let connector = self.connection();
+        let query_result = movies::table
+            .filter(movies::id.gt(after.unwrap_or(i32::MIN)))
+            .order(movies::id)
+            .limit(limit)
+            .load::<QueryableItem>(&connector)
+            .expect("Failed to fetch chunk of movies");
+
+        query_result.into_iter().map(|movie| MovieItem{id: movie.id, name: movie.title}).collect()
+    }
     fn get_user_ratings(&self, uid: i32) -> HashMap<i32, f64> {
-        let user:QueryableUser = users::table.find(uid).get_result(&self.connector).unwrap();
-        let query_result = QueryableRating::belonging_to(&user).load::<QueryableRating>(&self.connector).unwrap();
+        let connector = self.connection();
+        let user:QueryableUser = users::table.find(uid).get_result(&connector).unwrap();
+        let query_result = QueryableRating::belonging_to(&user).load::<QueryableRating>(&connector).unwrap();
 
         let mut ratings_by_item = HashMap::new();
         for rating in &query_result {
@@ -150,24 +258,164 @@ impl DBManager<MovieUser, MovieItem> for MovieDBManager {
 
         ratings_by_item
     }
-    fn get_users_with_ratings_chunk(&self, offset: i64, limit: i64) -> HashMap<i32, HashMap<i32, f64>> {
-        /*let mut users_with_ratings = HashMap::new();
+    fn get_users_with_ratings_chunk(&self, after: Option<i32>, limit: i64) -> HashMap<i32, HashMap<i32, f64>> {
+        let connector = self.connection();
+        let user_chunk = users::table
+            .filter(users::id.gt(after.unwrap_or(i32::MIN)))
+            .order(users::id)
+            .limit(limit)
+            .load::<QueryableUser>(&connector)
+            .expect("Failed to fetch chunk of users");
 
-        for uid in users_id {
-            let user:QueryableUser = users::table.find(uid).get_result(&self.connector).unwrap();
-            let query_result = QueryableRating::belonging_to(&user).load::<QueryableRating>(&self.connector).unwrap();
-            let mut ratings_by_item = HashMap::new();
-            for rating in &query_result {
-                ratings_by_item.insert(rating.movie_id, rating.rating);
-            }
+        let query_result = QueryableRating::belonging_to(&user_chunk)
+            .load::<QueryableRating>(&connector)
+            .expect("Failed to fetch chunk of ratings");
 
-            users_with_ratings.insert(*uid, ratings_by_item);
+        let mut users_with_ratings = HashMap::new();
+        for rating in query_result {
+            users_with_ratings.entry(rating.user_id).or_insert_with(HashMap::new).insert(rating.movie_id, rating.rating);
         }
-        
-        users_with_ratings*/
-        HashMap::new()
+
+        users_with_ratings
     }
+    fn get_item_ratings(&self, iid: i32) -> HashMap<i32, f64> {
+        let connector = self.connection();
+        let query_result = ratings::table
+            .filter(ratings::movie_id.eq(iid))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed query of ratings of the movie");
+
+        query_result.into_iter().map(|rating| (rating.user_id, rating.rating)).collect()
+    }
+    fn get_items_with_ratings_chunk(&self, after: Option<i32>, limit: i64) -> HashMap<i32, HashMap<i32, f64>> {
+        let connector = self.connection();
+        let item_chunk = movies::table
+            .select(movies::id)
+            .filter(movies::id.gt(after.unwrap_or(i32::MIN)))
+            .order(movies::id)
+            .limit(limit)
+            .load::<i32>(&connector)
+            .expect("Failed to fetch chunk of movies");
 
-    
-    
-}
\ No newline at end of file
+        let query_result = ratings::table
+            .filter(ratings::movie_id.eq_any(item_chunk))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed to fetch chunk of ratings");
+
+        let mut items_with_ratings = HashMap::new();
+        for rating in query_result {
+            items_with_ratings.entry(rating.movie_id).or_insert_with(HashMap::new).insert(rating.user_id, rating.rating);
+        }
+
+        items_with_ratings
+    }
+    fn get_item_rating_counts(&self) -> HashMap<i32, i64> {
+        let connector = self.connection();
+        let query_result = ratings::table
+            .group_by(ratings::movie_id)
+            .select((ratings::movie_id, sql::<BigInt>("COUNT(*)")))
+            .load::<(i32, i64)>(&connector)
+            .expect("Failed to count the ratings of every movie");
+
+        query_result.into_iter().collect()
+    }
+    fn count_users(&self) -> i64 {
+        let connector = self.connection();
+        users::table.count().get_result(&connector).expect("Failed to count the users")
+    }
+    fn count_items(&self) -> i64 {
+        let connector = self.connection();
+        movies::table.count().get_result(&connector).expect("Failed to count the movies")
+    }
+    fn count_ratings(&self) -> i64 {
+        let connector = self.connection();
+        ratings::table
+            .count()
+            .get_result(&connector)
+            .expect("Failed to count the ratings")
+    }
+    fn get_ratings_after(&self, after: i64, limit: i64) -> Vec<RatingRow<i32, i32>> {
+        let after = match i32::try_from(after) {
+            Ok(after) => after,
+            // No id of the column is past it, or every id is
+            Err(_) if after > 0 => return Vec::new(),
+            Err(_) => i32::MIN
+        };
+
+        let connector = self.connection();
+        let query_result = ratings::table
+            .filter(ratings::id.gt(after))
+            .order(ratings::id)
+            .limit(limit)
+            .load::<QueryableRating>(&connector)
+            .expect("Failed to fetch the page of ratings");
+
+        query_result.into_iter()
+            .map(|rating| RatingRow{key: rating.id as i64, user: rating.user_id, item: rating.movie_id, rating: rating.rating})
+            .collect()
+    }
+
+    fn get_all_ratings_of(&self, kind: RatingKind) -> HashMap<i32, HashMap<i32, f64>> {
+        match kind {
+            RatingKind::Explicit => self.get_all_ratings(),
+            RatingKind::Implicit => HashMap::new()
+        }
+    }
+
+    fn get_user_ratings_of(&self, uid: i32, kind: RatingKind) -> HashMap<i32, f64> {
+        match kind {
+            RatingKind::Explicit => self.get_user_ratings(uid),
+            RatingKind::Implicit => HashMap::new()
+        }
+    }
+
+    fn add_rating(&self, uid: i32, iid: i32, rating: f64) -> Result<(), WriteError> {
+        if !self.rating_scale().contains(rating) {
+            return Err(WriteError::OutOfScale(rating));
+        }
+
+        let connector = self.connection();
+        if !diesel::select(diesel::dsl::exists(users::table.find(uid))).get_result::<bool>(&connector)? {
+            return Err(WriteError::UnknownUser);
+        }
+        if !diesel::select(diesel::dsl::exists(movies::table.find(iid))).get_result::<bool>(&connector)? {
+            return Err(WriteError::UnknownItem);
+        }
+
+        diesel::insert_into(ratings::table)
+            .values(&NewRating{user_id: uid, movie_id: iid, rating})
+            .on_conflict((ratings::user_id, ratings::movie_id))
+            .do_update()
+            .set(ratings::rating.eq(rating))
+            .execute(&connector)?;
+
+        Ok(())
+    }
+
+    fn delete_rating(&self, uid: i32, iid: i32) -> bool {
+        let connector = self.connection();
+        let deleted = diesel::delete(ratings::table.filter(ratings::user_id.eq(uid)).filter(ratings::movie_id.eq(iid)))
+            .execute(&connector)
+            .expect("Failed to delete the rating");
+
+        deleted > 0
+    }
+
+    fn create_user(&self, name: &str, _data: &HashMap<String, String>) -> Result<MovieUser, WriteError> {
+        let connector = self.connection();
+        let user = diesel::insert_into(users::table)
+            .values(&NewUser{username: String::from(name)})
+            .get_result::<QueryableUser>(&connector)?;
+
+        Ok(MovieUser{id: user.id, name: user.username, ratings: HashMap::new()})
+    }
+
+    fn create_item(&self, name: &str, _data: &HashMap<String, String>) -> Result<MovieItem, WriteError> {
+        let connector = self.connection();
+        let movie = diesel::insert_into(movies::table)
+            .values(&NewMovie{title: String::from(name)})
+            .get_result::<QueryableItem>(&connector)?;
+
+        Ok(MovieItem{id: movie.id, name: movie.title})
+    }
+}
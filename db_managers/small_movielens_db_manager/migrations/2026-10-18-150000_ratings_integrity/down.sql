@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX ratings_movie_id_idx;
+ALTER TABLE ratings DROP CONSTRAINT ratings_user_id_movie_id_key;
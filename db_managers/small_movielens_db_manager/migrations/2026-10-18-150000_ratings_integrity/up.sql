@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- Keeps the latest inserted rating of every duplicated (user, item) pair
+DELETE FROM ratings duplicated
+    USING ratings kept
+    WHERE duplicated.user_id = kept.user_id
+        AND duplicated.movie_id = kept.movie_id
+        AND duplicated.id < kept.id;
+
+-- The unique index also serves the lookups by user_id
+ALTER TABLE ratings ADD CONSTRAINT ratings_user_id_movie_id_key UNIQUE (user_id, movie_id);
+CREATE INDEX ratings_movie_id_idx ON ratings (movie_id);
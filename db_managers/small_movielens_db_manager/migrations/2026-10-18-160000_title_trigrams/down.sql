@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX movies_title_trgm_idx;
@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM load_checkpoints;
+ALTER TABLE load_checkpoints
+    DROP COLUMN fingerprint,
+    DROP COLUMN last_key,
+    ADD COLUMN chunks_done INTEGER NOT NULL;
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- Chunk counts can't be resumed from, the loads they belong to start over
+DELETE FROM load_checkpoints;
+ALTER TABLE load_checkpoints
+    DROP COLUMN chunks_done,
+    ADD COLUMN fingerprint VARCHAR NOT NULL,
+    ADD COLUMN last_key VARCHAR NOT NULL;
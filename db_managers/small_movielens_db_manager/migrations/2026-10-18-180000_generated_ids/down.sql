@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE movies ALTER COLUMN id DROP DEFAULT;
+DROP SEQUENCE movies_id_seq;
+
+ALTER TABLE users ALTER COLUMN id DROP DEFAULT;
+DROP SEQUENCE users_id_seq;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- Users and movies created by the manager take their id from the sequences, the importer moves them past the loaded ids
+CREATE SEQUENCE users_id_seq OWNED BY users.id;
+SELECT setval('users_id_seq', COALESCE(MAX(id), 0) + 1, false) FROM users;
+ALTER TABLE users ALTER COLUMN id SET DEFAULT nextval('users_id_seq');
+
+CREATE SEQUENCE movies_id_seq OWNED BY movies.id;
+SELECT setval('movies_id_seq', COALESCE(MAX(id), 0) + 1, false) FROM movies;
+ALTER TABLE movies ALTER COLUMN id SET DEFAULT nextval('movies_id_seq');
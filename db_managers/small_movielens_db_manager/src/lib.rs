@@ -11,7 +11,7 @@ pub mod small_movielens_db_manager;
 #[cfg(test)]
 mod tests {
     use super::small_movielens_db_manager::SmallMovielensDBManager;
-    use db_manager::{DBManager};
+    use db_manager::{Connect, DBManager, Item, MatchMode, UserQuery};
 
     #[test]
     fn query_user() {
@@ -25,6 +25,10 @@ mod tests {
         
         println!("{:?}\n", users);
 
+        let users = manager.get_users_by_query(&UserQuery{min_ratings: Some(1000), limit: Some(5), ..Default::default()});
+
+        println!("{:?}\n", users);
+
         //Dont do this, it takes a lot of time.. REFACTORING
         //let users = manager.get_all_users();
         
@@ -34,6 +38,10 @@ mod tests {
         
         println!("{:?}\n", movies);
 
+        let movies = manager.search_items("jumanji", MatchMode::Substring, 5);
+
+        println!("{:?}\n", movies);
+
         let movies = manager.get_item_by_id(2);
         
         println!("{:?}\n", movies);
@@ -46,4 +54,54 @@ mod tests {
 
         println!("{:?}\n", users);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn item_ratings() {
+        let manager = SmallMovielensDBManager::connect_to("postgres://ademir:@localhost/small_movielens");
+
+        let jumanji = manager.get_item_ratings(2);
+        println!("{:?}\n", jumanji);
+
+        let counts = manager.get_item_rating_counts();
+        assert_eq!(counts[&2], jumanji.len() as i64);
+
+        let chunk = manager.get_items_with_ratings_chunk(None, 5);
+        assert_eq!(chunk[&2], jumanji);
+        assert!(chunk.iter().all(|(item, ratings)| counts[item] == ratings.len() as i64));
+    }
+
+    #[test]
+    fn fuzzy_search() {
+        let manager = SmallMovielensDBManager::connect_to("postgres://ademir:@localhost/small_movielens");
+
+        let movies = manager.search_items("Toy Stroy", MatchMode::Fuzzy, 3);
+        println!("{:?}\n", movies);
+
+        assert_eq!(movies.len(), 3);
+        assert!(movies.iter().all(|movie| movie.item.name().starts_with("Toy Story")));
+    }
+
+    #[test]
+    fn keyset_chunks() {
+        let manager = SmallMovielensDBManager::connect_to("postgres://ademir:@localhost/small_movielens");
+
+        let mut users = Vec::new();
+        let mut ratings = 0;
+        let mut after = None;
+        loop {
+            let chunk = manager.get_users_chunk(after, 100);
+            if chunk.is_empty() {
+                break;
+            }
+
+            ratings += manager.get_users_with_ratings_chunk(after, 100).values().map(|rated| rated.len() as i64).sum::<i64>();
+            after = chunk.last().copied();
+            users.extend(chunk);
+        }
+        println!("{} users, {} items, {} ratings\n", manager.count_users(), manager.count_items(), manager.count_ratings());
+
+        assert!(users.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(users.len() as i64, manager.count_users());
+        assert_eq!(ratings, manager.count_ratings());
+    }
+}
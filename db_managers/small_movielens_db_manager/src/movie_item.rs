@@ -14,7 +14,6 @@ pub struct QueryableItem {
 #[derive(Insertable)]
 #[table_name="movies"]
 pub struct NewMovie {
-    pub id: i32,
     pub title: String,
     pub genres: String,
 }
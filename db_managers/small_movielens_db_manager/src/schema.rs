@@ -1,3 +1,11 @@
+table! {
+    load_checkpoints (stage) {
+        stage -> Varchar,
+        fingerprint -> Varchar,
+        last_key -> Varchar,
+    }
+}
+
 table! {
     movies (id) {
         id -> Int4,
@@ -25,6 +33,7 @@ joinable!(ratings -> movies (movie_id));
 joinable!(ratings -> users (user_id));
 
 allow_tables_to_appear_in_same_query!(
+    load_checkpoints,
     movies,
     ratings,
     users,
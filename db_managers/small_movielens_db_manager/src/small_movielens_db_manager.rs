@@ -1,30 +1,50 @@
 use std::collections::HashMap;
+use std::convert::TryFrom;
 
 use diesel::prelude::*;
-use diesel::pg::PgConnection;
+use diesel::dsl::sql;
+use diesel::sql_types::{BigInt, Bool, Float, Text};
 
-use db_manager::DBManager;
+use db_manager::{Connect, DBManager, MatchMode, RatingKind, RatingRow, RatingScale, SearchResult, UserQuery, WriteError, search};
+use db_manager::pool::{PgPool, PgPooledConnection, connect_pool};
 
 use crate::schema::{users, movies, ratings};
-use crate::{movie_user::{SMovieLensUser, QueryableUser}, movie_item::{SMovieLensItem, QueryableItem}, movie_rating::{QueryableRating}};
+use crate::{movie_user::{SMovieLensUser, QueryableUser}, movie_item::{SMovieLensItem, QueryableItem, NewMovie}, movie_rating::{QueryableRating, NewRating}};
 
 pub struct SmallMovielensDBManager {
-    connector:PgConnection
+    pool: PgPool
 }
 
-impl DBManager<SMovieLensUser, SMovieLensItem> for SmallMovielensDBManager {
+impl SmallMovielensDBManager {
+    /// Shares the connections of another manager of the same database.
+    pub fn from_pool(pool: PgPool) -> SmallMovielensDBManager {
+        SmallMovielensDBManager{pool}
+    }
+
+    fn connection(&self) -> PgPooledConnection {
+        self.pool.get().expect("Failed to get a connection from the pool")
+    }
+}
+
+impl Connect for SmallMovielensDBManager {
     fn connect_to(url: &str) -> Self {
-        let connector = PgConnection::establish(url).expect("Failed connection to database. Maybe the URL?");
-        SmallMovielensDBManager{connector: connector}
+        SmallMovielensDBManager{pool: connect_pool(url)}
+    }
+}
+
+impl DBManager<SMovieLensUser, SMovieLensItem> for SmallMovielensDBManager {
+    fn rating_scale(&self) -> RatingScale {
+        RatingScale::create(0.5, 5.0, Some(0.5))
     }
 
     fn get_user_by_name(&self, name: &str) -> Vec<SMovieLensUser> {
-        vec![]
+        self.get_users_by_query(&UserQuery{name: Some(String::from(name)), ..Default::default()})
     }
 
     fn get_user_by_id(&self, uid: i32) -> Vec<SMovieLensUser> {
+        let connector = self.connection();
         let query_result = users::table.filter(users::id.eq(uid))
-            .load::<QueryableUser>(&self.connector)
+            .load::<QueryableUser>(&connector)
             .expect("Failed query of user with the uid specified");
 
         if query_result.is_empty() {
@@ -34,7 +54,7 @@ impl DBManager<SMovieLensUser, SMovieLensItem> for SmallMovielensDBManager {
         let selected_user = &query_result[0];
 
         let query_result = ratings::table.filter(ratings::user_id.eq(selected_user.id))
-            .load::<QueryableRating>(&self.connector)
+            .load::<QueryableRating>(&connector)
             .expect(&format!("Failed query of ratings of the user {}", selected_user.id));
 
         let mut user_ratings = HashMap::new();
@@ -45,9 +65,45 @@ impl DBManager<SMovieLensUser, SMovieLensItem> for SmallMovielensDBManager {
         vec![SMovieLensUser{id:selected_user.id, ratings:user_ratings}]
     }
 
+    fn get_users_by_query(&self, query: &UserQuery) -> Vec<SMovieLensUser> {
+        if query.name.is_some() || query.city.is_some() || query.state.is_some() || query.country.is_some() || query.min_age.is_some() || query.max_age.is_some() {
+            return Vec::new();
+        }
+
+        let mut selection = users::table.order(users::id).into_boxed();
+        if let Some(min_ratings) = query.min_ratings {
+            selection = selection.filter(sql::<Bool>("(SELECT COUNT(*) FROM ratings WHERE ratings.user_id = users.id) >= ").bind::<BigInt, _>(min_ratings));
+        }
+        if let Some(offset) = query.offset {
+            selection = selection.offset(offset);
+        }
+        if let Some(limit) = query.limit {
+            selection = selection.limit(limit);
+        }
+
+        let connector = self.connection();
+        let query_result = selection
+            .load::<QueryableUser>(&connector)
+            .expect("Failed query of users with the given criteria");
+
+        let ratings_by_user = QueryableRating::belonging_to(&query_result)
+            .load::<QueryableRating>(&connector)
+            .expect("Failed query of ratings of the selected users")
+            .grouped_by(&query_result);
+
+        let mut result = Vec::new();
+        for (selected_user, user_ratings) in query_result.into_iter().zip(ratings_by_user) {
+            let user_ratings = user_ratings.into_iter().map(|rating| (rating.movie_id, rating.rating)).collect();
+            result.push(SMovieLensUser{id: selected_user.id, ratings: user_ratings});
+        }
+
+        result
+    }
+
     fn get_item_by_name(&self, name: &str) -> Vec<SMovieLensItem> {
+        let connector = self.connection();
         let query_result = movies::table.filter(movies::title.eq(name))
-            .load::<QueryableItem>(&self.connector)
+            .load::<QueryableItem>(&connector)
             .expect("Failed query of movie with the given title");
 
         let mut result = Vec::new();
@@ -66,8 +122,9 @@ impl DBManager<SMovieLensUser, SMovieLensItem> for SmallMovielensDBManager {
     }
 
     fn get_item_by_id(&self, uid: i32) -> Vec<SMovieLensItem> {
+        let connector = self.connection();
         let query_result = movies::table.filter(movies::id.eq(uid))
-            .load::<QueryableItem>(&self.connector)
+            .load::<QueryableItem>(&connector)
             .expect("Failed query of movie with the given uid");
 
         if query_result.is_empty() {
@@ -79,16 +136,68 @@ impl DBManager<SMovieLensUser, SMovieLensItem> for SmallMovielensDBManager {
         vec![SMovieLensItem::create(movie.id, movie.title.clone(), movie.genres.clone())]
     }
 
+    fn get_users_by_ids(&self, uids: &[i32]) -> HashMap<i32, SMovieLensUser> {
+        let connector = self.connection();
+        let query_result = users::table.filter(users::id.eq_any(uids))
+            .load::<QueryableUser>(&connector)
+            .expect("Failed query of users with the given uids");
+
+        let ratings_by_user = QueryableRating::belonging_to(&query_result)
+            .load::<QueryableRating>(&connector)
+            .expect("Failed query of ratings of the selected users")
+            .grouped_by(&query_result);
+
+        let mut result = HashMap::new();
+        for (selected_user, user_ratings) in query_result.into_iter().zip(ratings_by_user) {
+            let user_ratings = user_ratings.into_iter().map(|rating| (rating.movie_id, rating.rating)).collect();
+            result.insert(selected_user.id, SMovieLensUser{id: selected_user.id, ratings: user_ratings});
+        }
+
+        result
+    }
+
+    fn get_items_by_ids(&self, iids: &[i32]) -> HashMap<i32, SMovieLensItem> {
+        let connector = self.connection();
+        let query_result = movies::table.filter(movies::id.eq_any(iids))
+            .load::<QueryableItem>(&connector)
+            .expect("Failed query of movies with the given uids");
+
+        query_result.into_iter().map(|movie| (movie.id, SMovieLensItem::create(movie.id, movie.title, movie.genres))).collect()
+    }
+
+    fn search_items(&self, query: &str, mode: MatchMode, limit: usize) -> Vec<SearchResult<SMovieLensItem>> {
+        let connector = self.connection();
+        let candidates = match search::like_pattern(query, mode) {
+            Some(pattern) => movies::table.filter(movies::title.ilike(pattern)).load::<QueryableItem>(&connector),
+            // Similar titles, or titles with a part similar to the query, by the trigram index
+            None => movies::table
+                .filter(sql::<Bool>("title % ").bind::<Text, _>(query).sql(" OR ").bind::<Text, _>(query).sql(" <% title"))
+                .order(sql::<Float>("word_similarity(").bind::<Text, _>(query).sql(", title) DESC"))
+                .limit(search::FUZZY_CANDIDATES)
+                .load::<QueryableItem>(&connector)
+        }.expect("Failed search of movies with the given query");
+
+        let candidates = candidates.into_iter().map(|movie| {
+            let title = movie.title.clone();
+            (SMovieLensItem::create(movie.id, movie.title, movie.genres), title)
+        }).collect();
+
+        search::rank(query, mode, candidates, limit).into_iter()
+            .map(|(item, score)| SearchResult{item, score})
+            .collect()
+    }
+
     fn get_all_users(&self) -> Vec<SMovieLensUser> {
+        let connector = self.connection();
         let query_result = users::table
-            .load::<QueryableUser>(&self.connector)
+            .load::<QueryableUser>(&connector)
             .expect("Failed query of all users");
 
         let mut result = Vec::new();
 
         for selected_user in &query_result {
             let query_result = ratings::table.filter(ratings::user_id.eq(selected_user.id))
-                .load::<QueryableRating>(&self.connector)
+                .load::<QueryableRating>(&connector)
                 .expect(&format!("Failed query of ratings of the user {}", selected_user.id));
             
             let mut user_ratings = HashMap::new();
@@ -103,8 +212,9 @@ impl DBManager<SMovieLensUser, SMovieLensItem> for SmallMovielensDBManager {
     }
 
     fn get_all_ratings(&self) -> HashMap<i32, HashMap<i32, f64>> {
+        let connector = self.connection();
         let query_result = ratings::table
-            .load::<QueryableRating>(&self.connector)
+            .load::<QueryableRating>(&connector)
             .expect("Failed to fetch all ratings");
 
         let mut result = HashMap::new();
@@ -117,19 +227,33 @@ impl DBManager<SMovieLensUser, SMovieLensItem> for SmallMovielensDBManager {
         }
         result
     }
-    fn get_users_chunk(&self, offset: i64, limit: i64) -> Vec<i32> {
+    fn get_users_chunk(&self, after: Option<i32>, limit: i64) -> Vec<i32> {
+        let connector = self.connection();
         let user_chunk = users::table
             .select(users::id)
+            .filter(users::id.gt(after.unwrap_or(i32::MIN)))
+            .order(users::id)
             .limit(limit)
-            .offset(offset)
-            .load::<i32>(&self.connector)
+            .load::<i32>(&connector)
             .expect("Failed to fetch chunk of users");
         
         user_chunk
     }
+    fn get_items_chunk(&self, after: Option<i32>, limit: i64) -> Vec<SMovieLensItem> {
+        let connector = self.connection();
+        let query_result = movies::table
+            .filter(movies::id.gt(after.unwrap_or(i32::MIN)))
+            .order(movies::id)
+            .limit(limit)
+            .load::<QueryableItem>(&connector)
+            .expect("Failed to fetch chunk of movies");
+
+        query_result.into_iter().map(|movie| SMovieLensItem::create(movie.id, movie.title, movie.genres)).collect()
+    }
     fn get_user_ratings(&self, uid: i32) -> HashMap<i32, f64> {
-        let user:QueryableUser = users::table.find(uid).get_result(&self.connector).unwrap();
-        let query_result = QueryableRating::belonging_to(&user).load::<QueryableRating>(&self.connector).unwrap();
+        let connector = self.connection();
+        let user:QueryableUser = users::table.find(uid).get_result(&connector).unwrap();
+        let query_result = QueryableRating::belonging_to(&user).load::<QueryableRating>(&connector).unwrap();
 
         let mut ratings_by_item = HashMap::new();
         for rating in &query_result {
@@ -138,14 +262,16 @@ impl DBManager<SMovieLensUser, SMovieLensItem> for SmallMovielensDBManager {
 
         ratings_by_item
     }
-    fn get_users_with_ratings_chunk(&self, offset: i64, limit: i64) -> HashMap<i32, HashMap<i32, f64>> {
+    fn get_users_with_ratings_chunk(&self, after: Option<i32>, limit: i64) -> HashMap<i32, HashMap<i32, f64>> {
+        let connector = self.connection();
         let user_chunk = users::table
+            .filter(users::id.gt(after.unwrap_or(i32::MIN)))
+            .order(users::id)
             .limit(limit)
-            .offset(offset)
-            .load::<QueryableUser>(&self.connector)
+            .load::<QueryableUser>(&connector)
             .expect("Failed to fetch chunk of users");
 
-        let query_result = QueryableRating::belonging_to(&user_chunk).load::<QueryableRating>(&self.connector).expect("Failed to fetch chunk of ratings");
+        let query_result = QueryableRating::belonging_to(&user_chunk).load::<QueryableRating>(&connector).expect("Failed to fetch chunk of ratings");
 
         let mut users_with_ratings = HashMap::new();
         for rating in query_result {
@@ -158,7 +284,149 @@ impl DBManager<SMovieLensUser, SMovieLensItem> for SmallMovielensDBManager {
 
         users_with_ratings
     }
-    
-    
+    fn get_item_ratings(&self, iid: i32) -> HashMap<i32, f64> {
+        let connector = self.connection();
+        let query_result = ratings::table
+            .filter(ratings::movie_id.eq(iid))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed query of ratings of the movie");
 
-}
\ No newline at end of file
+        query_result.into_iter().map(|rating| (rating.user_id, rating.rating)).collect()
+    }
+    fn get_items_with_ratings_chunk(&self, after: Option<i32>, limit: i64) -> HashMap<i32, HashMap<i32, f64>> {
+        let connector = self.connection();
+        let item_chunk = movies::table
+            .select(movies::id)
+            .filter(movies::id.gt(after.unwrap_or(i32::MIN)))
+            .order(movies::id)
+            .limit(limit)
+            .load::<i32>(&connector)
+            .expect("Failed to fetch chunk of movies");
+
+        let query_result = ratings::table
+            .filter(ratings::movie_id.eq_any(item_chunk))
+            .load::<QueryableRating>(&connector)
+            .expect("Failed to fetch chunk of ratings");
+
+        let mut items_with_ratings = HashMap::new();
+        for rating in query_result {
+            items_with_ratings.entry(rating.movie_id).or_insert_with(HashMap::new).insert(rating.user_id, rating.rating);
+        }
+
+        items_with_ratings
+    }
+    fn get_item_rating_counts(&self) -> HashMap<i32, i64> {
+        let connector = self.connection();
+        let query_result = ratings::table
+            .group_by(ratings::movie_id)
+            .select((ratings::movie_id, sql::<BigInt>("COUNT(*)")))
+            .load::<(i32, i64)>(&connector)
+            .expect("Failed to count the ratings of every movie");
+
+        query_result.into_iter().collect()
+    }
+    fn count_users(&self) -> i64 {
+        let connector = self.connection();
+        users::table.count().get_result(&connector).expect("Failed to count the users")
+    }
+    fn count_items(&self) -> i64 {
+        let connector = self.connection();
+        movies::table.count().get_result(&connector).expect("Failed to count the movies")
+    }
+    fn count_ratings(&self) -> i64 {
+        let connector = self.connection();
+        ratings::table
+            .count()
+            .get_result(&connector)
+            .expect("Failed to count the ratings")
+    }
+    fn get_ratings_after(&self, after: i64, limit: i64) -> Vec<RatingRow<i32, i32>> {
+        let after = match i32::try_from(after) {
+            Ok(after) => after,
+            // No id of the column is past it, or every id is
+            Err(_) if after > 0 => return Vec::new(),
+            Err(_) => i32::MIN
+        };
+
+        let connector = self.connection();
+        let query_result = ratings::table
+            .filter(ratings::id.gt(after))
+            .order(ratings::id)
+            .limit(limit)
+            .load::<QueryableRating>(&connector)
+            .expect("Failed to fetch the page of ratings");
+
+        query_result.into_iter()
+            .map(|rating| RatingRow{key: rating.id as i64, user: rating.user_id, item: rating.movie_id, rating: rating.rating})
+            .collect()
+    }
+
+    fn get_all_ratings_of(&self, kind: RatingKind) -> HashMap<i32, HashMap<i32, f64>> {
+        match kind {
+            RatingKind::Explicit => self.get_all_ratings(),
+            RatingKind::Implicit => HashMap::new()
+        }
+    }
+
+    fn get_user_ratings_of(&self, uid: i32, kind: RatingKind) -> HashMap<i32, f64> {
+        match kind {
+            RatingKind::Explicit => self.get_user_ratings(uid),
+            RatingKind::Implicit => HashMap::new()
+        }
+    }
+
+    fn add_rating(&self, uid: i32, iid: i32, rating: f64) -> Result<(), WriteError> {
+        if !self.rating_scale().contains(rating) {
+            return Err(WriteError::OutOfScale(rating));
+        }
+
+        let connector = self.connection();
+        if !diesel::select(diesel::dsl::exists(users::table.find(uid))).get_result::<bool>(&connector)? {
+            return Err(WriteError::UnknownUser);
+        }
+        if !diesel::select(diesel::dsl::exists(movies::table.find(iid))).get_result::<bool>(&connector)? {
+            return Err(WriteError::UnknownItem);
+        }
+
+        diesel::insert_into(ratings::table)
+            .values(&NewRating{user_id: uid, movie_id: iid, rating})
+            .on_conflict((ratings::user_id, ratings::movie_id))
+            .do_update()
+            .set(ratings::rating.eq(rating))
+            .execute(&connector)?;
+
+        Ok(())
+    }
+
+    fn delete_rating(&self, uid: i32, iid: i32) -> bool {
+        let connector = self.connection();
+        let deleted = diesel::delete(ratings::table.filter(ratings::user_id.eq(uid)).filter(ratings::movie_id.eq(iid)))
+            .execute(&connector)
+            .expect("Failed to delete the rating");
+
+        deleted > 0
+    }
+
+    /// MovieLens users are only an id, taken from the users sequence.
+    fn create_user(&self, _name: &str, _data: &HashMap<String, String>) -> Result<SMovieLensUser, WriteError> {
+        let connector = self.connection();
+        let id = diesel::insert_into(users::table)
+            .default_values()
+            .returning(users::id)
+            .get_result::<i32>(&connector)?;
+
+        Ok(SMovieLensUser{id, ratings: HashMap::new()})
+    }
+
+    /// The genres are read from the "Genres" key as in `data()`.
+    fn create_item(&self, name: &str, data: &HashMap<String, String>) -> Result<SMovieLensItem, WriteError> {
+        let genres = data.get("Genres").cloned().unwrap_or_else(|| String::from("(no genres listed)"));
+
+        let connector = self.connection();
+        let movie = diesel::insert_into(movies::table)
+            .values(&NewMovie{title: String::from(name), genres})
+            .get_result::<QueryableItem>(&connector)?;
+
+        Ok(SMovieLensItem::create(movie.id, movie.title, movie.genres))
+    }
+}
@@ -1,18 +1,24 @@
-use::std::fmt::Debug;
+use::std::fmt::{Debug, Display};
 
 use std::collections::HashMap;
 use std::collections::BinaryHeap;
 use std::hash::Hash;
 use std::marker::PhantomData;
 use std::cmp::{Reverse, min};
+use std::env;
+use std::process;
+use std::str::FromStr;
 
-use db_manager::{DBManager, User, Item};
+use db_manager::{Connect, DBManager, User, Item, Normalization, Normalizer, CachingManager, CacheConfig};
+use db_manager::dynamic::{DynUser, DynItem};
+use dataset_tools::registry;
 use simple_movie_db_manager::{movie_db_manager::MovieDBManager, movie_user::MovieUser, movie_item::MovieItem};
 use book_db_manager::{book_db_manager::BookDBManager, book_user::BookUser, book_item::BookItem};
 use small_movielens_db_manager::{small_movielens_db_manager::SmallMovielensDBManager, movie_user::SMovieLensUser, movie_item::SMovieLensItem};
 
 pub mod simple_movie_interface;
 pub mod small_movielens_interface;
+pub mod resolution;
 
 #[derive(Clone,PartialEq)]
 enum KNNMetric {
@@ -329,13 +335,19 @@ fn small_movielens_knn(k: i32, target_id: String, metric: KNNMetric) {
     let target_user = target_user[0].clone();
     let target_ratings = target_user.ratings();
 
-    const USERS_NUMBER:i64 = 610;
     let chunk_size:i64 = k as i64;
 
     let mut nearest_neighbors = Vec::new();
 
-    for chunk_it in (0..USERS_NUMBER).step_by(chunk_size as usize) {
-        let users_with_ratings = manager.get_users_with_ratings_chunk(chunk_it, chunk_size);
+    let mut after = None;
+    loop {
+        let users = manager.get_users_chunk(after, chunk_size);
+        if users.is_empty() {
+            break;
+        }
+
+        let users_with_ratings = manager.get_users_with_ratings_chunk(after, chunk_size);
+        after = users.last().copied();
 
         let chunk_knn = engine.k_nearest_neighbors(k, target_id, &target_ratings, &users_with_ratings, &metric);
         nearest_neighbors = auxiliar.merge_heap_results_for_knn(k, &nearest_neighbors, &chunk_knn, &metric);
@@ -352,7 +364,211 @@ fn small_movielens_knn(k: i32, target_id: String, metric: KNNMetric) {
 
 }
 
+fn prediction_with_k_neighbors<M, U, I>(
+manager: &M, k: i32,
+target_name: Option<String>, target_id: Option<U::ID>,
+item_name: Option<String>, item_id: Option<I::ID>,
+metric: KNNMetric, normalization: Normalization
+)
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    U::ID: Hash+Eq+Clone+Debug+Display,
+    I::ID: Hash+Eq+Clone+Display
+{
+    let engine = Engine::<U::ID, I::ID> {phantom_U: PhantomData, phantom_I: PhantomData};
+
+    let target_user = match resolution::resolve_user(manager, target_name.as_deref(), target_id) {
+        Ok(user) => user,
+        Err(error) => {
+            println!("Failed to resolve the user for prediction. {}", error);
+            return;
+        }
+    };
+    let item = match resolution::resolve_item(manager, item_name.as_deref(), item_id) {
+        Ok(item) => item,
+        Err(error) => {
+            println!("Failed to resolve the item for prediction. {}", error);
+            return;
+        }
+    };
+    if let Some(name) = item_name.as_deref().filter(|name| *name != item.name()) {
+        println!("No item named '{}', using the closest match '{}'", name, item.name());
+    }
+
+    let all_ratings = manager.get_all_ratings();
+    let target_ratings = target_user.ratings();
+    let neighbors = engine.k_nearest_neighbors(k, target_user.id(), &target_ratings, &all_ratings, &metric);
+
+    let scale = manager.rating_scale();
+    let target_normalizer = Normalizer::fit(normalization, scale, &target_ratings);
+
+    let neighbor_ids: Vec<U::ID> = neighbors.iter().map(|neighbor| neighbor.id.clone()).collect();
+    let neighbor_users = manager.get_users_by_ids(&neighbor_ids);
+
+    let mut predicted_rating = 0.0;
+    let mut pearson_total = 0.0;
+    for neighbor in &neighbors {
+        let neighbor_ratings = match neighbor_users.get(&neighbor.id) {
+            Some(user) => user.ratings(),
+            None => continue
+        };
+        let pearson = engine.pearson_correlation_between(&neighbor_ratings, &target_ratings);
+        if !pearson.is_finite() {
+            continue;
+        }
+
+        if let Some(rating_item) = neighbor_ratings.get(&item.id()) {
+            let neighbor_normalizer = Normalizer::fit(normalization, scale, &neighbor_ratings);
+            predicted_rating += neighbor_normalizer.normalize(*rating_item)*pearson;
+            pearson_total += pearson;
+            println!("Neighbor {} with weight {} rated the item {} with: {}", neighbor.id, pearson, item.name(), rating_item);
+        } else {
+            println!("Neighbor {} didn't rated the item", neighbor.id);
+        }
+    }
+
+    let predicted_rating = target_normalizer.denormalize(predicted_rating/pearson_total);
+    if !predicted_rating.is_finite() {
+        println!("No neighbor rated the item, the rating can't be predicted");
+        return;
+    }
+
+    println!("The value predicted is {}", scale.clamp(predicted_rating));
+}
+
+/// Recommends the items the neighbors of the user rated and the user didn't, weighting each
+/// neighbor's ratings by their share of the total similarity.
+fn recommend_with_k_neighbors<M, U, I>(manager: &M, k: i32, target_name: Option<String>, target_id: Option<U::ID>, rec_number: usize)
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    U::ID: Hash+Eq+Clone+Debug+Display,
+    I::ID: Hash+Eq+Clone+Display
+{
+    let engine = Engine::<U::ID, I::ID> {phantom_U: PhantomData, phantom_I: PhantomData};
+
+    let target_user = match resolution::resolve_user(manager, target_name.as_deref(), target_id) {
+        Ok(user) => user,
+        Err(error) => {
+            println!("Failed to resolve the user to recommend. {}", error);
+            return;
+        }
+    };
+
+    let all_ratings = manager.get_all_ratings();
+    let target_ratings = target_user.ratings();
+    let neighbors = engine.k_nearest_neighbors(k, target_user.id(), &target_ratings, &all_ratings, &KNNMetric::Pearson);
+
+    let neighbor_ids: Vec<U::ID> = neighbors.iter().map(|neighbor| neighbor.id.clone()).collect();
+    let neighbor_users = manager.get_users_by_ids(&neighbor_ids);
+    let total: f64 = neighbors.iter().map(|neighbor| neighbor.value).sum();
+
+    let mut recommendations: HashMap<I::ID, f64> = HashMap::new();
+    for neighbor in &neighbors {
+        let neighbor_user = match neighbor_users.get(&neighbor.id) {
+            Some(user) => user,
+            None => continue
+        };
+        let weight = neighbor.value/total;
+        for (item_id, rating) in neighbor_user.ratings() {
+            if !target_ratings.contains_key(&item_id) {
+                *recommendations.entry(item_id).or_insert(0.0) += rating*weight;
+            }
+        }
+    }
+
+    let mut sorted: Vec<PairDist<I::ID>> = recommendations.into_iter().map(|(id, value)| PairDist{id, value}).collect();
+    sorted.sort();
+    sorted.reverse();
+    sorted.truncate(rec_number);
+
+    let item_ids: Vec<I::ID> = sorted.iter().map(|recommendation| recommendation.id.clone()).collect();
+    let items = manager.get_items_by_ids(&item_ids);
+    for recommendation in &sorted {
+        let name = items.get(&recommendation.id).map(|item| item.name()).unwrap_or_default();
+        println!("{} ({}): {}", name, recommendation.id, recommendation.value);
+    }
+}
+
+const USAGE: &str = "Usage:
+  metrics-system predict <dataset> (--user NAME | --user-id ID) (--item NAME | --item-id ID) [--k N] [--mean-centering] [--cache-stats] [--config FILE]
+  metrics-system recommend <dataset> (--user NAME | --user-id ID) [--k N] [--count N] [--config FILE]
+  metrics-system similarities
+A name matching several users or items is narrowed down by also giving the id.
+--config is the datasets.toml giving where the datasets are, the one in the working directory by default";
+
 fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("predict") if args.len() >= 2 => predict(&args[1], &args[2..]),
+        Some("recommend") if args.len() >= 2 => recommend(&args[1], &args[2..]),
+        Some("similarities") => print_similarities(),
+        _ => exit_with_usage()
+    }
+}
+
+/// Predicts the rating of a user to an item of the dataset from its nearest neighbors by Pearson correlation.
+fn predict(dataset: &str, args: &[String]) {
+    let datasets = registry::datasets(&registry::config_path(args));
+    let manager = datasets.connect(dataset).unwrap_or_else(|| exit_with_usage());
+
+    let user_name: Option<String> = option(args, "--user");
+    let user_id = option::<String>(args, "--user-id").map(|raw| manager.parse_user_id(&raw).unwrap_or_else(|| exit_with_usage()));
+    let item_name: Option<String> = option(args, "--item");
+    let item_id = option::<String>(args, "--item-id").map(|raw| manager.parse_item_id(&raw).unwrap_or_else(|| exit_with_usage()));
+    let k = option(args, "--k").unwrap_or(3);
+    if k <= 0 {
+        exit_with_usage();
+    }
+    let normalization = if args.iter().any(|arg| arg == "--mean-centering") { Normalization::MeanCentering } else { Normalization::None };
+
+    let manager: CachingManager<_, DynUser, DynItem> = CachingManager::create(manager, CacheConfig::default());
+    prediction_with_k_neighbors(&manager, k, user_name, user_id, item_name, item_id, KNNMetric::Pearson, normalization);
+
+    if args.iter().any(|arg| arg == "--cache-stats") {
+        for (method, stats) in manager.stats() {
+            println!("{}: {}", method, stats);
+        }
+    }
+}
+
+/// Recommends to a user of the dataset the items its nearest neighbors by Pearson correlation rated best.
+fn recommend(dataset: &str, args: &[String]) {
+    let datasets = registry::datasets(&registry::config_path(args));
+    let manager = datasets.connect(dataset).unwrap_or_else(|| exit_with_usage());
+
+    let user_name: Option<String> = option(args, "--user");
+    let user_id = option::<String>(args, "--user-id").map(|raw| manager.parse_user_id(&raw).unwrap_or_else(|| exit_with_usage()));
+    let k = option(args, "--k").unwrap_or(3);
+    if k <= 0 {
+        exit_with_usage();
+    }
+    let count = option(args, "--count").unwrap_or(5);
+
+    recommend_with_k_neighbors(&manager, k, user_name, user_id, count);
+}
+
+/// Value following the flag `name`, exits with the usage if it's there but not valid.
+fn option<T: FromStr>(args: &[String], name: &str) -> Option<T> {
+    let position = args.iter().position(|arg| arg == name)?;
+    let value = args.get(position + 1).and_then(|value| value.parse().ok());
+    Some(value.unwrap_or_else(|| exit_with_usage()))
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    let config = registry::config_path(&env::args().collect::<Vec<_>>());
+    if config.exists() {
+        eprintln!("Datasets: {}", registry::configs(&config).keys().cloned().collect::<Vec<_>>().join(", "));
+    }
+    process::exit(1);
+}
+
+fn print_similarities() {
     //small_movielens_knn(500, String::from("1"), KNNMetric::Manhattan);
     let (simple_movie_order, simple_movie_matrix) = simple_movie_interface::get_similarity_matrix();
     let (small_movielens_order, small_movielens_matrix) = small_movielens_interface::get_similarity_matrix();
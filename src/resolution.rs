@@ -0,0 +1,96 @@
+use std::fmt::{self, Display};
+
+use db_manager::{DBManager, User, Item, MatchMode};
+
+/// Closest fuzzy matches considered when no title is equal to the name.
+const FUZZY_CANDIDATES: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct Candidate<ID> {
+    pub id: ID,
+    pub name: String
+}
+
+#[derive(Debug, Clone)]
+pub enum ResolutionError<ID> {
+    MissingTarget,
+    NotFound,
+    /// The name matched several targets, pick one of them by its ID.
+    Ambiguous(Vec<Candidate<ID>>)
+}
+
+impl<ID: Display> Display for ResolutionError<ID> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolutionError::MissingTarget => write!(f, "You need to specify a name or an id"),
+            ResolutionError::NotFound => write!(f, "Not found any target with the specified ID or Name"),
+            ResolutionError::Ambiguous(candidates) => {
+                writeln!(f, "The name matches {} targets, specify one of them by its id:", candidates.len())?;
+                for candidate in candidates {
+                    writeln!(f, "  {} ({})", candidate.id, candidate.name)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Resolves exactly one user. When the name matches several users the ID picks among them.
+pub fn resolve_user<M, U, I>(manager: &M, name: Option<&str>, id: Option<U::ID>) -> Result<U, ResolutionError<U::ID>>
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    U::ID: PartialEq
+{
+    let candidates = match (name, id) {
+        (None, None) => return Err(ResolutionError::MissingTarget),
+        (None, Some(id)) => manager.get_user_by_id(id),
+        (Some(name), id) => {
+            let users = manager.get_user_by_name(name);
+            match id {
+                Some(id) => users.into_iter().filter(|user| user.id() == id).collect(),
+                None => users
+            }
+        }
+    };
+
+    pick_one(candidates, |user| Candidate{id: user.id(), name: user.name()})
+}
+
+/// Resolves exactly one item, falling back to the closest fuzzy matches when no title is equal to the name.
+/// When the name matches several items the ID picks among them.
+pub fn resolve_item<M, U, I>(manager: &M, name: Option<&str>, id: Option<I::ID>) -> Result<I, ResolutionError<I::ID>>
+where
+    M: DBManager<U, I>,
+    U: User<I>,
+    I: Item,
+    I::ID: PartialEq
+{
+    let candidates = match (name, id) {
+        (None, None) => return Err(ResolutionError::MissingTarget),
+        (None, Some(id)) => manager.get_item_by_id(id),
+        (Some(name), id) => {
+            let mut items = manager.get_item_by_name(name);
+            if items.is_empty() {
+                items = manager.search_items(name, MatchMode::Fuzzy, FUZZY_CANDIDATES).into_iter()
+                    .map(|result| result.item)
+                    .collect();
+            }
+            match id {
+                Some(id) => items.into_iter().filter(|item| item.id() == id).collect(),
+                None => items
+            }
+        }
+    };
+
+    pick_one(candidates, |item| Candidate{id: item.id(), name: item.name()})
+}
+
+fn pick_one<T, ID>(mut candidates: Vec<T>, describe: impl Fn(&T) -> Candidate<ID>) -> Result<T, ResolutionError<ID>> {
+    match candidates.len() {
+        0 => Err(ResolutionError::NotFound),
+        1 => Ok(candidates.remove(0)),
+        _ => Err(ResolutionError::Ambiguous(candidates.iter().map(describe).collect()))
+    }
+}
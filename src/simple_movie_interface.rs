@@ -1,26 +1,28 @@
 use::std::fmt::Debug;
 
 use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
 
-use db_manager::{DBManager, User, Item};
+use db_manager::{Connect, DBManager};
 use simple_movie_db_manager::{movie_db_manager::MovieDBManager, movie_user::MovieUser, movie_item::MovieItem};
 
-fn get_items_by_id_or_name(item_name:Option<String>, item_id:Option<String>) -> Vec<MovieItem> {
-    let manager = MovieDBManager::connect_to("postgres://ademir:@localhost/simple_movies");
+use crate::resolution::{self, ResolutionError};
 
-    let mut item_targets = Vec::new();
-    if let Some(item_name) = item_name {
-        item_targets = manager.get_item_by_name(&item_name);
-    }
-    if let Some(item_id) = item_id {
-        item_targets = manager.get_item_by_id(item_id.parse().expect("Failed to parse item id"));
-    }
-    item_targets
+/// Shared by every call, so the connections of its pool are reused.
+fn manager() -> &'static MovieDBManager {
+    static MANAGER: OnceLock<MovieDBManager> = OnceLock::new();
+    MANAGER.get_or_init(|| MovieDBManager::connect_to("postgres://ademir:@localhost/simple_movies"))
 }
 
+fn get_items_by_id_or_name(item_name:Option<String>, item_id:Option<String>) -> Result<MovieItem, ResolutionError<i32>> {
+    let manager = manager();
+
+    let item_id = item_id.map(|item_id| item_id.parse().expect("Failed to parse item id"));
+    resolution::resolve_item(manager, item_name.as_deref(), item_id)
+}
 
 pub fn get_similarity_matrix() -> (Vec<i32>, Vec<Vec<f64>>){
-    let manager = MovieDBManager::connect_to("postgres://ademir:@localhost/simple_movies");
+    let manager = manager();
 
     let mut all_ratings = manager.get_all_ratings();
     let mut averages = HashMap::new();
@@ -96,16 +98,16 @@ s_item_name:Option<String>, s_item_id:Option<String>
         return None;
     }
 
-    let first_items = get_items_by_id_or_name(f_item_name, f_item_id);
-    let second_items = get_items_by_id_or_name(s_item_name, s_item_id);
+    let first_item = get_items_by_id_or_name(f_item_name, f_item_id);
+    let second_item = get_items_by_id_or_name(s_item_name, s_item_id);
 
-    if first_items.is_empty() || second_items.is_empty() {
-        println!("Failed to find the items. FirstResultSize:{}, SecondResultSize:{}", first_items.len(), second_items.len());
-        return None;
-    }
-
-    let first_item = &first_items[0];
-    let second_item = &second_items[0];
+    let (first_item, second_item) = match (first_item, second_item) {
+        (Ok(first_item), Ok(second_item)) => (first_item, second_item),
+        (Err(error), _) | (_, Err(error)) => {
+            println!("Failed to find the items. {}", error);
+            return None;
+        }
+    };
 
     let first_index = item_order.iter().position(|item| *item == first_item.id).expect("First item not found in similarity matrix");
     let second_index = item_order.iter().position(|item| *item == second_item.id).expect("Second item not found in similarity matrix");